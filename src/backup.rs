@@ -0,0 +1,162 @@
+use crate::error::{HookError, Result};
+use crate::git::is_managed_hook;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Hooks athira found in place when installing, kept in `.git/thira/backup/`
+/// so they can be chained or put back on uninstall
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HookBackup {
+    /// `core.hooksPath` as it was before athira replaced it
+    #[serde(default)]
+    pub hooks_path: Option<PathBuf>,
+    #[serde(default)]
+    pub hooks: Vec<BackedUpHook>,
+    #[serde(skip)]
+    dir: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackedUpHook {
+    pub name: String,
+    pub original: PathBuf,
+    /// File name of the copy inside the backup directory
+    pub file: String,
+    /// Whether git was running this hook when it was backed up
+    pub active: bool,
+}
+
+impl HookBackup {
+    const MANIFEST: &'static str = "manifest.json";
+
+    pub fn load(git_dir: &Path) -> Result<Self> {
+        let dir = git_dir.join("thira").join("backup");
+        let manifest = dir.join(Self::MANIFEST);
+        if !manifest.exists() {
+            return Ok(Self {
+                dir,
+                ..Default::default()
+            });
+        }
+
+        let content = std::fs::read_to_string(&manifest).map_err(|e| HookError::FileError {
+            path: manifest.clone(),
+            source: e,
+        })?;
+        let mut backup: Self = serde_json::from_str(&content).map_err(|e| {
+            HookError::ConfigError(format!(
+                "Invalid backup manifest {}: {}",
+                manifest.display(),
+                e
+            ))
+        })?;
+        backup.dir = dir;
+        Ok(backup)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if self.hooks.is_empty() && self.hooks_path.is_none() {
+            return Ok(());
+        }
+
+        std::fs::create_dir_all(&self.dir).map_err(|e| HookError::FileError {
+            path: self.dir.clone(),
+            source: e,
+        })?;
+        let manifest = self.dir.join(Self::MANIFEST);
+        let content = serde_json::to_string_pretty(self).map_err(|e| {
+            HookError::ConfigError(format!("Failed to encode backup manifest: {}", e))
+        })?;
+        std::fs::write(&manifest, content).map_err(|e| HookError::FileError {
+            path: manifest,
+            source: e,
+        })
+    }
+
+    pub fn contains(&self, original: &Path) -> bool {
+        self.hooks.iter().any(|hook| hook.original == original)
+    }
+
+    /// Copies the hook at `original` into the backup directory
+    pub fn add(&mut self, name: &str, original: &Path, active: bool) -> Result<PathBuf> {
+        std::fs::create_dir_all(&self.dir).map_err(|e| HookError::FileError {
+            path: self.dir.clone(),
+            source: e,
+        })?;
+
+        // Several directories may hold a hook with the same name
+        let count = self.hooks.iter().filter(|hook| hook.name == name).count();
+        let file = if count == 0 {
+            name.to_string()
+        } else {
+            format!("{}.{}", name, count)
+        };
+
+        let target = self.dir.join(&file);
+        std::fs::copy(original, &target).map_err(|e| HookError::FileError {
+            path: original.to_path_buf(),
+            source: e,
+        })?;
+
+        self.hooks.push(BackedUpHook {
+            name: name.to_string(),
+            original: original.to_path_buf(),
+            file,
+            active,
+        });
+        Ok(target)
+    }
+
+    /// Paths of the pre-existing hooks to run before athira's commands.
+    /// The original is preferred while it's still in place, since hooks from
+    /// other managers often source helper files next to themselves.
+    pub fn chained(&self, name: &str) -> Vec<PathBuf> {
+        self.hooks
+            .iter()
+            .filter(|hook| hook.name == name && hook.active)
+            .map(|hook| {
+                if hook.original.exists() && !is_managed_hook(&hook.original) {
+                    hook.original.clone()
+                } else {
+                    self.dir.join(&hook.file)
+                }
+            })
+            .collect()
+    }
+
+    /// Puts every backed up hook back where it was found and removes the
+    /// backup directory. Returns the restored paths.
+    pub fn restore(self) -> Result<Vec<PathBuf>> {
+        let mut restored = Vec::new();
+
+        for hook in &self.hooks {
+            // Never overwrite a hook someone added after the backup was taken
+            if hook.original.exists() && !is_managed_hook(&hook.original) {
+                continue;
+            }
+
+            if let Some(parent) = hook.original.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| HookError::FileError {
+                    path: parent.to_path_buf(),
+                    source: e,
+                })?;
+            }
+            std::fs::copy(self.dir.join(&hook.file), &hook.original).map_err(|e| {
+                HookError::FileError {
+                    path: hook.original.clone(),
+                    source: e,
+                }
+            })?;
+            restored.push(hook.original.clone());
+        }
+
+        if self.dir.exists() {
+            std::fs::remove_dir_all(&self.dir).map_err(|e| HookError::FileError {
+                path: self.dir.clone(),
+                source: e,
+            })?;
+        }
+
+        Ok(restored)
+    }
+}
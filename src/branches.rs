@@ -0,0 +1,144 @@
+use crate::config::BranchesConfig;
+use crate::error::{HookError, Result};
+use crate::git::{GitRepo, PushRef};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use regex::Regex;
+
+/// The `branches:` policy, checked before a hook's commands run
+pub struct BranchPolicy<'a> {
+    config: &'a BranchesConfig,
+    protected: GlobSet,
+    naming: Option<Regex>,
+}
+
+impl<'a> BranchPolicy<'a> {
+    pub fn new(config: &'a BranchesConfig) -> Result<Self> {
+        let mut protected = GlobSetBuilder::new();
+        for pattern in &config.protected {
+            // `release/*` shouldn't also protect `release/1.0/hotfix`
+            let glob = GlobBuilder::new(pattern)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| {
+                    HookError::ConfigError(format!(
+                        "Invalid protected branch pattern '{}': {}",
+                        pattern, e
+                    ))
+                })?;
+            protected.add(glob);
+        }
+        let protected = protected
+            .build()
+            .map_err(|e| HookError::ConfigError(format!("Invalid protected branches: {}", e)))?;
+
+        let naming = config
+            .naming
+            .as_deref()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|e| {
+                    HookError::ConfigError(format!(
+                        "Invalid branch naming pattern '{}': {}",
+                        pattern, e
+                    ))
+                })
+            })
+            .transpose()?;
+
+        Ok(Self {
+            config,
+            protected,
+            naming,
+        })
+    }
+
+    pub fn is_protected(&self, branch: &str) -> bool {
+        self.protected.is_match(branch)
+    }
+
+    /// Checks the policy for a hook invocation, `stdin` being the data git
+    /// passed to the hook
+    pub fn check(&self, hook: &str, args: &[String], stdin: Option<&[u8]>) -> Result<()> {
+        if self.config.is_empty() {
+            return Ok(());
+        }
+
+        match hook {
+            "pre-commit" | "pre-merge-commit" => {
+                if let Some(branch) = GitRepo::current_branch()? {
+                    if self.is_protected(&branch) {
+                        return Err(violation(
+                            &branch,
+                            "direct commits to protected branches are not allowed",
+                        ));
+                    }
+                }
+            }
+            // Only branch checkouts, not checkouts of single files
+            "post-checkout" if args.get(2).is_none_or(|flag| flag == "1") => {
+                if let Some(branch) = GitRepo::current_branch()? {
+                    self.check_name(&branch)?;
+                }
+            }
+            "pre-push" => {
+                let input = String::from_utf8_lossy(stdin.unwrap_or_default());
+                for push in PushRef::parse_all(&input) {
+                    self.check_push(&push)?;
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn check_name(&self, branch: &str) -> Result<()> {
+        let Some(naming) = &self.naming else {
+            return Ok(());
+        };
+
+        // Protected branches like main are exempt from the naming scheme
+        if !self.is_protected(branch) && !naming.is_match(branch) {
+            return Err(violation(
+                branch,
+                &format!("branch names must match '{}'", naming.as_str()),
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_push(&self, push: &PushRef) -> Result<()> {
+        if let Some(branch) = push.local_ref.strip_prefix("refs/heads/") {
+            if !push.is_delete() {
+                self.check_name(branch)?;
+            }
+        }
+
+        let Some(target) = push.remote_ref.strip_prefix("refs/heads/") else {
+            return Ok(());
+        };
+        if self.config.allow_force_push || !self.is_protected(target) || push.is_new() {
+            return Ok(());
+        }
+
+        if push.is_delete() {
+            return Err(violation(
+                target,
+                "protected branches can't be deleted from the remote",
+            ));
+        }
+        if !GitRepo::is_ancestor(&push.remote_sha, &push.local_sha)? {
+            return Err(violation(
+                target,
+                "force pushes to protected branches are not allowed",
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn violation(branch: &str, reason: &str) -> HookError {
+    HookError::BranchPolicyError {
+        branch: branch.to_string(),
+        reason: reason.to_string(),
+    }
+}
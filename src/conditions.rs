@@ -0,0 +1,89 @@
+use crate::config::Condition;
+use crate::error::{HookError, Result};
+use crate::git::GitRepo;
+use globset::GlobBuilder;
+use std::path::Path;
+
+/// Operating system names a condition can use, as Rust reports them
+pub const OPERATING_SYSTEMS: &[&str] = &[
+    "linux",
+    "macos",
+    "windows",
+    "freebsd",
+    "netbsd",
+    "openbsd",
+    "dragonfly",
+    "solaris",
+    "illumos",
+    "android",
+    "ios",
+];
+
+/// The part of `condition` that doesn't hold right now, like `os: windows`.
+/// None when the command should run.
+pub fn unmet(condition: &Condition) -> Result<Option<String>> {
+    let os = std::env::consts::OS;
+    if !holds(&condition.os, |name| Ok(name == os))? {
+        return Ok(Some(describe("os", &condition.os)));
+    }
+
+    if !condition.branch.is_empty() {
+        // Nothing matches a detached HEAD, e.g. during a rebase
+        let branch = GitRepo::current_branch()?;
+        let matches = |pattern: &str| match &branch {
+            Some(branch) => branch_glob(pattern).map(|glob| glob.is_match(branch)),
+            None => Ok(false),
+        };
+        if !holds(&condition.branch, matches)? {
+            return Ok(Some(describe("branch", &condition.branch)));
+        }
+    }
+
+    if !holds(&condition.env, |entry| Ok(env_matches(entry)))? {
+        return Ok(Some(describe("env", &condition.env)));
+    }
+
+    if !holds(&condition.exists, |file| Ok(Path::new(file).exists()))? {
+        return Ok(Some(describe("exists", &condition.exists)));
+    }
+
+    Ok(None)
+}
+
+/// Matches a branch name; `release/*` doesn't match `release/1.0/hotfix`
+pub fn branch_glob(pattern: &str) -> Result<globset::GlobMatcher> {
+    GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .map(|glob| glob.compile_matcher())
+        .map_err(|e| HookError::ConfigError(format!("Invalid branch pattern '{}': {}", pattern, e)))
+}
+
+// Whether any of the entries matches and none of the `!` entries does. A
+// setting with only `!` entries holds unless one of them matches.
+fn holds(entries: &[String], matches: impl Fn(&str) -> Result<bool>) -> Result<bool> {
+    let mut wanted = None;
+    for entry in entries {
+        match entry.strip_prefix('!') {
+            Some(excluded) => {
+                if matches(excluded)? {
+                    return Ok(false);
+                }
+            }
+            None => wanted = Some(wanted.unwrap_or(false) || matches(entry)?),
+        }
+    }
+    Ok(wanted.unwrap_or(true))
+}
+
+// `NAME` is set to a non-empty value, or `NAME=value` has that value
+fn env_matches(entry: &str) -> bool {
+    match entry.split_once('=') {
+        Some((name, value)) => std::env::var(name).is_ok_and(|actual| actual == value),
+        None => std::env::var(entry).is_ok_and(|value| !value.is_empty()),
+    }
+}
+
+fn describe(setting: &str, entries: &[String]) -> String {
+    format!("{}: {}", setting, entries.join(", "))
+}
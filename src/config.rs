@@ -1,14 +1,19 @@
 use crate::error::HookError;
+use crate::document::{self, Key};
+use crate::formats::{self, Format};
+use crate::layers::{self, Layers};
+use crate::validate;
 use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ScriptConfig {
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "is_default")]
     pub parallel: bool,
-    #[serde(default = "default_max_threads")]
+    #[serde(default = "default_max_threads", skip_serializing_if = "is_default_max_threads")]
     pub max_threads: usize,
     #[serde(default)]
     pub commands: Vec<CommandConfig>,
@@ -21,11 +26,36 @@ pub struct CommandConfig {
     pub description: Option<String>,
     #[serde(default)]
     pub working_dir: Option<PathBuf>,
-    #[serde(default)]
+    #[serde(default, serialize_with = "sorted")]
     pub env: HashMap<String, String>,
+    /// `.env` file of variables to set, overridden by `env`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_file: Option<PathBuf>,
+    /// Only run when this holds
+    #[serde(default, alias = "if", skip_serializing_if = "Option::is_none")]
+    pub when: Option<Condition>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+/// When a hook or script command runs. Each setting lists alternatives, any
+/// of which may match, and an entry starting with `!` must not match.
+/// Every setting given has to hold.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct Condition {
+    /// Operating systems, e.g. `linux`, `macos` or `windows`
+    #[serde(default, deserialize_with = "one_or_many", skip_serializing_if = "Vec::is_empty")]
+    pub os: Vec<String>,
+    /// Patterns of the current branch, e.g. `release/*`
+    #[serde(default, deserialize_with = "one_or_many", skip_serializing_if = "Vec::is_empty")]
+    pub branch: Vec<String>,
+    /// Environment variables that are set, or `NAME=value`
+    #[serde(default, deserialize_with = "one_or_many", skip_serializing_if = "Vec::is_empty")]
+    pub env: Vec<String>,
+    /// Files that exist, relative to the repository root
+    #[serde(default, deserialize_with = "one_or_many", skip_serializing_if = "Vec::is_empty")]
+    pub exists: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct LinterConfig {
     #[serde(default)]
     pub types: Vec<String>,
@@ -39,31 +69,332 @@ pub struct LinterConfig {
     pub max_body_line_length: usize,
 }
 
+// The same lengths as a `lint` section that leaves them out
+impl Default for LinterConfig {
+    fn default() -> Self {
+        Self {
+            types: Vec::new(),
+            scopes: Vec::new(),
+            min_subject_length: default_min_subject_length(),
+            max_subject_length: default_max_subject_length(),
+            max_body_line_length: default_max_body_line_length(),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
+    /// Files and presets this configuration builds on, merged before it
+    #[serde(default, deserialize_with = "one_or_many", skip_serializing_if = "Vec::is_empty")]
+    pub extends: Vec<String>,
+    /// Shared hook repositories whose definitions hooks can `use`
+    #[serde(default)]
+    pub repos: Vec<RepoConfig>,
+    #[serde(default, serialize_with = "sorted")]
     pub hooks: HashMap<String, Vec<Hook>>,
+    #[serde(default, serialize_with = "sorted")]
     pub scripts: HashMap<String, ScriptConfig>,
     #[serde(default)]
     pub options: Options,
-    #[serde(default)]
+    // Left out when unset, so it doesn't hide the rules of a file this one extends
+    #[serde(default, skip_serializing_if = "is_default")]
     pub lint: LinterConfig,
+    #[serde(default)]
+    pub secrets: SecretsConfig,
+    #[serde(default)]
+    pub branches: BranchesConfig,
+    #[serde(default)]
+    pub policies: PoliciesConfig,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Limits on what a commit may contain, checked on pre-commit
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PoliciesConfig {
+    /// Maximum number of files changed
+    #[serde(default)]
+    pub max_files: Option<usize>,
+    /// Maximum number of lines added across all files
+    #[serde(default)]
+    pub max_added_lines: Option<usize>,
+    /// Patterns of files that must never be committed, e.g. `*.env`
+    #[serde(default)]
+    pub forbidden_paths: Vec<String>,
+    /// Files that have to change together
+    #[serde(default)]
+    pub companions: Vec<CompanionRule>,
+    /// Require `Cargo.lock` to change with the dependencies in `Cargo.toml`
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub cargo_lock: bool,
+}
+
+impl PoliciesConfig {
+    pub fn is_empty(&self) -> bool {
+        self.max_files.is_none()
+            && self.max_added_lines.is_none()
+            && self.forbidden_paths.is_empty()
+            && self.companions.is_empty()
+            && !self.cargo_lock
+    }
+}
+
+/// Changing a file matching `when` requires also changing one matching `require`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CompanionRule {
+    #[serde(deserialize_with = "one_or_many")]
+    pub when: Vec<String>,
+    #[serde(deserialize_with = "one_or_many")]
+    pub require: Vec<String>,
+}
+
+/// Rules for branch names and for what may happen on protected branches
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct BranchesConfig {
+    /// Branch patterns like `main` or `release/*` that can't be committed
+    /// to directly or force pushed
+    #[serde(default)]
+    pub protected: Vec<String>,
+    /// Regex every other branch name must match
+    #[serde(default)]
+    pub naming: Option<String>,
+    /// Allow force pushes to protected branches
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub allow_force_push: bool,
+}
+
+impl BranchesConfig {
+    pub fn is_empty(&self) -> bool {
+        self.protected.is_empty() && self.naming.is_none()
+    }
+
+    /// Hooks that enforce this policy
+    pub fn hooks(&self) -> Vec<&'static str> {
+        let mut hooks = Vec::new();
+        if !self.protected.is_empty() {
+            hooks.extend(["pre-commit", "pre-merge-commit"]);
+        }
+        if self.naming.is_some() {
+            hooks.push("post-checkout");
+        }
+        if !self.is_empty() {
+            hooks.push("pre-push");
+        }
+        hooks
+    }
+}
+
+/// Settings for `thira check secrets`
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SecretsConfig {
+    /// Additional patterns reported as secrets
+    #[serde(default)]
+    pub rules: Vec<SecretRule>,
+    /// File of patterns for findings to ignore (default `.thira-secrets-allow`)
+    #[serde(default)]
+    pub allowlist: Option<PathBuf>,
+    /// Minimum Shannon entropy (bits per character) of a generic secret
+    #[serde(default)]
+    pub entropy_threshold: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SecretRule {
+    pub id: String,
+    pub regex: String,
+}
+
+/// A git repository providing hook definitions, pinned to a revision
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RepoConfig {
+    /// Git URL or local path
+    pub repo: String,
+    /// Tag, branch or commit to check out
+    pub rev: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Hook {
+    /// Identifier used to reference this command, e.g. in `SKIP=<id>`
+    #[serde(default)]
+    pub id: Option<String>,
+    /// Id of a hook defined in one of the `repos`, used instead of `command`
+    #[serde(default)]
+    pub uses: Option<String>,
+    #[serde(default)]
     pub command: String,
     #[serde(default)]
     pub args: Vec<String>,
+    /// Only run for these files, e.g. `*.rs` or `src/**/*.ts`
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub glob: Vec<String>,
+    /// Leave out files matching these patterns
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub exclude: Vec<String>,
     #[serde(default)]
     pub working_dir: Option<PathBuf>,
+    /// Maximum run time in seconds before the command is killed
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    /// Report a failure as a warning without failing the hook
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub allow_failure: bool,
+    /// Keep running the remaining commands, then fail the hook
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub continue_on_error: bool,
+    /// Number of additional attempts after a failed run
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub retries: u32,
+    /// Run through `sh -c` (the default) or execute the command directly
+    #[serde(default)]
+    pub shell: Option<bool>,
+    /// Toolchain providing the command, in an environment managed by athira
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub language: Language,
+    /// Packages installed into the hook's environment, e.g. `prettier@3.3.3`
+    #[serde(default)]
+    pub additional_dependencies: Vec<String>,
+    /// Only run when this holds
+    #[serde(default, alias = "if", skip_serializing_if = "Option::is_none")]
+    pub when: Option<Condition>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+/// Where a hook's command comes from
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    /// Whatever is on the developer's PATH
+    #[default]
+    System,
+    /// A local npm prefix
+    Node,
+    /// A virtualenv
+    Python,
+    /// A `cargo install` root
+    Rust,
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::System => "system",
+            Self::Node => "node",
+            Self::Python => "python",
+            Self::Rust => "rust",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Options {
     #[serde(default)]
     pub auto_install: bool,
-    #[serde(default = "default_hooks_dir")]
+    #[serde(default = "default_hooks_dir", skip_serializing_if = "String::is_empty")]
     pub hooks_dir: String,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub migrate_existing: MigrateExisting,
+}
+
+// Used when a file leaves out the options section, as one that only
+// extends a preset does
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            auto_install: false,
+            hooks_dir: default_hooks_dir(),
+            migrate_existing: MigrateExisting::default(),
+        }
+    }
+}
+
+/// What to do with hooks that athira didn't write when installing
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrateExisting {
+    /// Back them up and restore them on uninstall
+    #[default]
+    Backup,
+    /// Back them up and also run them before athira's commands
+    Chain,
+}
+
+// Sets the value at `path`, adding the mappings leading to it
+fn set_at(tree: &mut Value, path: &[Key], value: Value) -> crate::error::Result<()> {
+    let Some((key, parents)) = path.split_last() else {
+        *tree = value;
+        return Ok(());
+    };
+
+    let mut parent = tree;
+    for (depth, key) in parents.iter().enumerate() {
+        parent = child(parent, key, &path[..=depth])?;
+    }
+    match (parent, key) {
+        (Value::Mapping(mapping), Key::Field(name)) => {
+            mapping.insert(name.as_str().into(), value);
+        }
+        (parent, key) => *child(parent, key, path)? = value,
+    }
+    Ok(())
+}
+
+fn child<'a>(
+    value: &'a mut Value,
+    key: &Key,
+    path: &[Key],
+) -> crate::error::Result<&'a mut Value> {
+    if value.is_null() {
+        *value = Value::Mapping(Default::default());
+    }
+    let found = match (value, key) {
+        (Value::Mapping(mapping), Key::Field(name)) => Some(
+            mapping
+                .entry(name.as_str().into())
+                .or_insert(Value::Mapping(Default::default())),
+        ),
+        (Value::Sequence(items), Key::Index(index)) => items.get_mut(*index),
+        _ => None,
+    };
+    found.ok_or_else(|| {
+        HookError::ConfigError(format!("There's no '{}' to set", document::dotted(path)))
+    })
+}
+
+// Accepts a single string where a list is expected
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
+// Serializes a map with its keys in order, so saved files are stable
+fn sorted<S, V>(map: &HashMap<String, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+    V: Serialize,
+{
+    map.iter()
+        .collect::<std::collections::BTreeMap<_, _>>()
+        .serialize(serializer)
+}
+
+// Leaves default values out of saved files
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+fn is_default_max_threads(value: &usize) -> bool {
+    *value == default_max_threads()
 }
 
 // Default values
@@ -109,108 +440,96 @@ impl fmt::Display for ScriptConfig {
 
 
 impl Config {
+    /// The effective configuration, merged from the user's config.yaml,
+    /// hooks.yaml and hooks.local.yaml
     pub fn load() -> crate::error::Result<Self> {
-        let config_path = PathBuf::from("hooks.yaml");
-        if !config_path.exists() {
-            return Ok(Self::default());
-        }
-        let content = std::fs::read_to_string(config_path)?;
-        Ok(serde_yaml::from_str(&content)?)
+        Ok(Self::load_layers()?.0)
     }
 
-    pub fn save(&self) -> crate::error::Result<()> {
-        let mut content = String::new();
-
-        // Hooks section
-        content.push_str("hooks:\n");
-        for (name, hooks) in &self.hooks {
-            content.push_str(&format!("  {}:\n", name));
-            for hook in hooks {
-                content.push_str("    - command: ");
-                content.push_str(&hook.command);
-                content.push('\n');
-
-                if !hook.args.is_empty() {
-                    content.push_str("      args:\n");
-                    for arg in &hook.args {
-                        content.push_str(&format!("        - {}\n", arg));
-                    }
-                }
-
-                if let Some(working_dir) = &hook.working_dir {
-                    content.push_str(&format!("      working_dir: {}\n", working_dir.display()));
+    /// The effective configuration along with the file each value came from
+    pub fn load_layers() -> crate::error::Result<(Self, Layers)> {
+        let layers = Layers::load(serde_yaml::to_value(Self::default())?)?;
+        let config = match serde_yaml::from_value(layers.value.clone()) {
+            Ok(config) => config,
+            Err(e) => {
+                // Point at the offending file, line and column when possible
+                let problems = validate::check()?;
+                if problems.is_empty() {
+                    return Err(e.into());
                 }
+                return Err(HookError::InvalidConfig(problems));
             }
-        }
-
-        // Scripts section with new format
-        content.push_str("\nscripts:\n");
-        for (name, script) in &self.scripts {
-            content.push_str(&format!("  {}:\n", name));
-
-            // Write parallel and max_threads if parallel is true or there are multiple commands
-            if script.parallel || script.commands.len() > 1 {
-                content.push_str(&format!("    parallel: {}\n", script.parallel));
-                content.push_str(&format!("    max_threads: {}\n", script.max_threads));
-            }
-
-            // Always write commands section
-            content.push_str("    commands:\n");
-            for cmd in &script.commands {
-                content.push_str(&format!("      - command: \"{}\"\n", cmd.command));
+        };
+        Ok((config, layers))
+    }
 
-                if let Some(desc) = &cmd.description {
-                    content.push_str(&format!("        description: \"{}\"\n", desc));
-                }
+    /// Only the repository's configuration file, the file `save` writes
+    pub fn load_repository() -> crate::error::Result<Self> {
+        match layers::read_file(&layers::repository_file())? {
+            Some(value) => Ok(serde_yaml::from_value(value)?),
+            None => Ok(Self::default()),
+        }
+    }
 
-                if let Some(dir) = &cmd.working_dir {
-                    content.push_str(&format!("        working_dir: \"{}\"\n", dir.display()));
-                }
+    /// Applies a change to this configuration and saves it to the
+    /// repository's file, leaving settings from the user and local files
+    /// out of it
+    pub fn update(&mut self, change: impl Fn(&mut Self)) -> crate::error::Result<()> {
+        let mut repository = Self::load_repository()?;
+        change(&mut repository);
+        change(self);
+        repository.save()
+    }
 
-                if !cmd.env.is_empty() {
-                    content.push_str("        env:\n");
-                    for (key, value) in &cmd.env {
-                        content.push_str(&format!("          {}: \"{}\"\n", key, value));
-                    }
-                }
-            }
+    /// Sets one setting of the repository's file by its path, like
+    /// `lint.max_subject_length`, or removes it when `value` is None. The
+    /// rest of the file is kept as it is, and the result is checked before
+    /// it's written.
+    pub fn set_value(path: &[Key], value: Option<Value>) -> crate::error::Result<()> {
+        let file = layers::repository_file();
+        let mut tree = layers::read_file(&file)?.unwrap_or(Value::Mapping(Default::default()));
+        match value {
+            Some(value) => set_at(&mut tree, path, value)?,
+            None => document::remove_at(&mut tree, path).ok_or_else(|| {
+                HookError::ConfigError(format!(
+                    "'{}' isn't set in {}",
+                    document::dotted(path),
+                    formats::describe(&file)
+                ))
+            })?,
         }
 
-        // Options section
-        content.push_str("\noptions:\n");
-        content.push_str(&format!("  auto_install: {}\n", self.options.auto_install));
-        content.push_str(&format!("  hooks_dir: {}\n", self.options.hooks_dir));
-
-        // Linter section
-        content.push_str("\nlint:\n");
-        if !self.lint.types.is_empty() {
-            content.push_str("  types:\n");
-            for t in &self.lint.types {
-                content.push_str(&format!("    - {}\n", t));
-            }
+        let mut problems = validate::check_value(&tree);
+        if let Ok(config) = serde_yaml::from_value(tree.clone()) {
+            problems.extend(validate::check_config(&config));
         }
-        if !self.lint.scopes.is_empty() {
-            content.push_str("  scopes:\n");
-            for s in &self.lint.scopes {
-                content.push_str(&format!("    - {}\n", s));
-            }
+        if !problems.is_empty() {
+            return Err(HookError::RejectedChange(problems));
         }
-        content.push_str(&format!(
-            "  min_subject_length: {}\n",
-            self.lint.min_subject_length
-        ));
-        content.push_str(&format!(
-            "  max_subject_length: {}\n",
-            self.lint.max_subject_length
-        ));
-        content.push_str(&format!(
-            "  max_body_line_length: {}\n",
-            self.lint.max_body_line_length
-        ));
-
-        // Write the content to file
-        std::fs::write("hooks.yaml", content)?;
 
+        let content = match Format::of(&file) {
+            Format::Yaml => {
+                let existing = std::fs::read_to_string(&file).ok();
+                document::write(existing.as_deref(), &tree, |content| {
+                    Ok(serde_yaml::from_str(content)?)
+                })?
+            }
+            _ => formats::write(&file, &tree)?,
+        };
+        std::fs::write(&file, content).map_err(|e| HookError::FileError {
+            path: file.clone(),
+            source: e,
+        })?;
+
+        Self::load()?.install_if_enabled()
+    }
+
+    pub fn save(&self) -> crate::error::Result<()> {
+        self.write_to(&layers::repository_file())?;
+        self.install_if_enabled()
+    }
+
+    fn install_if_enabled(&self) -> crate::error::Result<()> {
         // Auto-install hooks if enabled
         if self.options.auto_install {
             println!("Installing hooks auto_install...");
@@ -220,65 +539,79 @@ impl Config {
         Ok(())
     }
 
-    pub fn validate(&self) -> crate::error::Result<()> {
-        self.validate_hooks()?;
-        self.validate_lint_config()?;
-        self.validate_hooks_dir()?;
-        Ok(())
+    /// Writes this configuration to a file in the file's format
+    pub fn write_to(&self, path: &Path) -> crate::error::Result<()> {
+        let content = self.render(path)?;
+        std::fs::write(path, content).map_err(|e| HookError::FileError {
+            path: path.to_path_buf(),
+            source: e,
+        })
     }
 
-    fn validate_hooks_dir(&self) -> crate::error::Result<()> {
-        if self.options.hooks_dir == ".git" {
-            return Err(HookError::ConfigError(
-                "Invalid hooks directory: Cannot use '.git' directly. Use '.git/hooks' instead."
-                    .to_string(),
-            ));
+    /// The content of `path` with this configuration written to it. YAML
+    /// files and the sections of shared TOML and JSON files are updated in
+    /// place.
+    pub fn render(&self, path: &Path) -> crate::error::Result<String> {
+        match Format::of(path) {
+            Format::Yaml => {
+                let existing = if path.exists() {
+                    Some(std::fs::read_to_string(path).map_err(|e| HookError::FileError {
+                        path: path.to_path_buf(),
+                        source: e,
+                    })?)
+                } else {
+                    None
+                };
+                document::write(existing.as_deref(), &serde_yaml::to_value(self)?, |content| {
+                    Ok(serde_yaml::to_value(serde_yaml::from_str::<Self>(content)?)?)
+                })
+            }
+            _ => formats::write(path, self),
         }
-        Ok(())
     }
 
-    fn validate_hooks(&self) -> crate::error::Result<()> {
-        for (name, hooks) in &self.hooks {
-            if hooks.is_empty() {
-                return Err(HookError::ConfigError(format!(
-                    "Hook '{}' has no commands",
-                    name
-                )));
-            }
-
-            for hook in hooks {
-                if hook.command.is_empty() {
-                    return Err(HookError::ConfigError(format!(
-                        "Empty command in hook '{}'",
-                        name
-                    )));
-                }
-            }
+    /// Hooks that enforce the branch and commit policies
+    pub fn policy_hooks(&self) -> Vec<&'static str> {
+        let mut hooks = self.branches.hooks();
+        if !self.policies.is_empty() {
+            hooks.push("pre-commit");
         }
-        Ok(())
+        hooks
     }
 
-    fn validate_lint_config(&self) -> crate::error::Result<()> {
-        if self.lint.min_subject_length == 0 {
-            return Err(HookError::ConfigError(
-                "min_subject_length must be greater than 0".into(),
-            ));
-        }
-        if self.lint.max_subject_length < self.lint.min_subject_length {
-            return Err(HookError::ConfigError(
-                "max_subject_length must be greater than min_subject_length".into(),
-            ));
+    /// Every hook athira installs: those with commands plus those
+    /// enforcing a policy
+    pub fn hook_names(&self) -> Vec<String> {
+        let mut names = self
+            .hooks
+            .keys()
+            .cloned()
+            .chain(self.policy_hooks().into_iter().map(str::to_string))
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Checks the configuration files and the configuration they make up,
+    /// reporting every problem found at once
+    pub fn validate(&self) -> crate::error::Result<()> {
+        let mut problems = validate::check_files()?;
+        let mut found = validate::check_config(self);
+        if let Ok(layers) = Layers::load(serde_yaml::to_value(Self::default())?) {
+            validate::locate(&mut found, &layers)?;
         }
-        if self.lint.max_body_line_length == 0 {
-            return Err(HookError::ConfigError(
-                "max_body_line_length must be greater than 0".into(),
-            ));
+        problems.extend(found);
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(HookError::InvalidConfig(problems))
         }
-        Ok(())
     }
 
     pub fn add_script(&mut self, name: String, command: String) -> crate::error::Result<()> {
-        self.scripts.insert(name, ScriptConfig {
+        let script = ScriptConfig {
             parallel: false,
             max_threads: default_max_threads(),
             commands: vec![
@@ -287,20 +620,52 @@ impl Config {
                     description: None,
                     working_dir: None,
                     env: HashMap::new(),
+                    env_file: None,
+                    when: None,
                 }
             ],
-        });
-        self.save()?;
-        Ok(())
+        };
+        self.update(|config| {
+            config.scripts.insert(name.clone(), script.clone());
+        })
     }
 
     pub fn remove_script(&mut self, name: &str) -> crate::error::Result<()> {
-        self.scripts.remove(name);
-        self.save()?;
-        Ok(())
+        self.update(|config| {
+            config.scripts.remove(name);
+        })
+    }
+
+    /// Adds a commit type to the effective list of types, so the types of
+    /// files this one extends are kept
+    pub fn add_lint_type(&self, name: &str) -> crate::error::Result<()> {
+        if self.lint.types.iter().any(|existing| existing == name) {
+            return Err(HookError::ConfigError(format!(
+                "'{}' is already a commit type",
+                name
+            )));
+        }
+        let mut types = self.lint.types.clone();
+        types.push(name.to_string());
+        Self::set_value(&lint_types(), Some(serde_yaml::to_value(types)?))
+    }
+
+    pub fn remove_lint_type(&self, name: &str) -> crate::error::Result<()> {
+        if !self.lint.types.iter().any(|existing| existing == name) {
+            return Err(HookError::ConfigError(format!(
+                "'{}' isn't a commit type",
+                name
+            )));
+        }
+        let types: Vec<_> = self.lint.types.iter().filter(|existing| *existing != name).collect();
+        Self::set_value(&lint_types(), Some(serde_yaml::to_value(types)?))
     }
 }
 
+fn lint_types() -> Vec<Key> {
+    vec![Key::Field("lint".into()), Key::Field("types".into())]
+}
+
 impl Default for Config {
     fn default() -> Self {
         let mut hooks = HashMap::new();
@@ -324,6 +689,8 @@ impl Default for Config {
                             env.insert("TEST_VALUE".to_string(), "123".to_string());
                             env
                         },
+                        env_file: None,
+                        when: None,
                     },
                     CommandConfig {
                         command: "sh test2.sh".to_string(),
@@ -335,6 +702,8 @@ impl Default for Config {
                             env.insert("TEST_VALUE".to_string(), "456".to_string());
                             env
                         },
+                        env_file: None,
+                        when: None,
                     },
                 ],
             },
@@ -348,12 +717,12 @@ impl Default for Config {
                 Hook {
                     command: "cargo".to_string(),
                     args: vec!["test".to_string()],
-                    working_dir: None,
+                    ..Default::default()
                 },
                 Hook {
                     command: "cargo".to_string(),
                     args: vec!["clippy".to_string()],
-                    working_dir: None,
+                    ..Default::default()
                 },
             ],
         );
@@ -368,7 +737,7 @@ impl Default for Config {
                     "validate".to_string(),
                     "$1".to_string(),
                 ],
-                working_dir: None,
+                ..Default::default()
             }],
         );
 
@@ -403,13 +772,18 @@ impl Default for Config {
         };
 
         Self {
+            extends: Vec::new(),
+            repos: Vec::new(),
             hooks,
             scripts,
             options: Options {
                 auto_install: true,
-                hooks_dir: ".thira".to_string(),
+                ..Options::default()
             },
             lint,
+            secrets: SecretsConfig::default(),
+            branches: BranchesConfig::default(),
+            policies: PoliciesConfig::default(),
         }
     }
 }
@@ -0,0 +1,1103 @@
+use crate::error::{HookError, Result};
+use serde_yaml::Value;
+
+// Upper bound on the edits of one save, against edits that make no progress
+const MAX_EDITS: usize = 10_000;
+
+/// A step in a path through the configuration, like `hooks` or `[0]`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Key {
+    Field(String),
+    Index(usize),
+}
+
+/// A path in the dotted form used by `thira config show`, like
+/// `hooks.pre-commit[0].command`
+pub fn dotted(path: &[Key]) -> String {
+    let mut dotted = String::new();
+    for key in path {
+        match key {
+            Key::Field(name) if dotted.is_empty() => dotted.push_str(name),
+            Key::Field(name) => dotted.push_str(&format!(".{}", name)),
+            Key::Index(index) => dotted.push_str(&format!("[{}]", index)),
+        }
+    }
+    dotted
+}
+
+/// Parses a dotted path like `hooks.pre-commit[0].command`. None if it's
+/// malformed, e.g. with an empty name or an unclosed `[`.
+pub fn parse_dotted(dotted: &str) -> Option<Vec<Key>> {
+    let mut path = Vec::new();
+    for part in dotted.split('.') {
+        let (name, mut rest) = part.find('[').map_or((part, ""), |at| part.split_at(at));
+        if name.is_empty() {
+            return None;
+        }
+        path.push(Key::Field(name.to_string()));
+        while !rest.is_empty() {
+            let (index, after) = rest.strip_prefix('[')?.split_once(']')?;
+            path.push(Key::Index(index.parse().ok()?));
+            rest = after;
+        }
+    }
+    Some(path)
+}
+
+/// The value at `path`, if there is one
+pub fn value_at<'a>(value: &'a Value, path: &[Key]) -> Option<&'a Value> {
+    path.iter().try_fold(value, |value, key| match key {
+        Key::Field(key) => value.get(key.as_str()),
+        Key::Index(index) => value.get(index),
+    })
+}
+
+/// Removes the value at `path`. None if it isn't there.
+pub fn remove_at(tree: &mut Value, path: &[Key]) -> Option<()> {
+    let (key, parents) = path.split_last()?;
+    let mut parent = tree;
+    for key in parents {
+        parent = match key {
+            Key::Field(name) => parent.get_mut(name.as_str())?,
+            Key::Index(index) => parent.get_mut(index)?,
+        };
+    }
+    match (parent, key) {
+        (Value::Mapping(mapping), Key::Field(name)) => mapping.remove(name.as_str()).map(|_| ()),
+        (Value::Sequence(items), Key::Index(index)) if *index < items.len() => {
+            items.remove(*index);
+            Some(())
+        }
+        _ => None,
+    }
+}
+
+/// Line and column, both starting at 1, of the deepest part of `path` that
+/// exists in a YAML document
+pub fn position(content: &str, path: &[Key]) -> Option<(usize, usize)> {
+    let document = Document::new(content);
+    let root = document.root()?;
+    let slot = locate(&root, path).pop()?;
+    Some((slot.line + 1, slot.col + 1))
+}
+
+#[derive(Debug)]
+enum Edit {
+    /// Write the target's value at a path, replacing or adding it
+    Set(Vec<Key>),
+    Remove(Vec<Key>),
+}
+
+/// Writes `target` as YAML. An existing file is changed with minimal edits
+/// that keep its comments, key order, anchors and formatting, and is never
+/// rewritten in full: if editing fails, so does the write. Without a file,
+/// the whole file is rendered. `normalize` parses YAML into the same shape
+/// as `target`, and is used to check that the result reads back as
+/// `target`.
+pub fn write(
+    existing: Option<&str>,
+    target: &Value,
+    normalize: impl Fn(&str) -> Result<Value>,
+) -> Result<String> {
+    if let Some(content) = existing {
+        return Document::new(content)
+            .update(target, &normalize)
+            .map_err(|e| {
+                let reason = match e {
+                    HookError::ConfigError(reason) => reason,
+                    e => e.to_string(),
+                };
+                HookError::ConfigError(format!(
+                    "Can't change the configuration without losing its comments or formatting \
+                     ({}), so it was left as it is. Make the change by hand instead.",
+                    reason
+                ))
+            });
+    }
+
+    let content = render(target);
+    if normalize(&content)? != *target {
+        return Err(HookError::ConfigError(
+            "The configuration can't be written as YAML".into(),
+        ));
+    }
+    Ok(content)
+}
+
+/// Renders a whole configuration file, with a blank line between sections
+pub fn render(value: &Value) -> String {
+    let Value::Mapping(mapping) = value else {
+        return format!("{}\n", inline(value, false).unwrap_or_default());
+    };
+
+    let sections = mapping
+        .iter()
+        .filter(|(_, value)| !is_empty(value))
+        .map(|(key, value)| entry_lines(&scalar(key), value, 0, false).join("\n"))
+        .collect::<Vec<_>>();
+    format!("{}\n", sections.join("\n\n"))
+}
+
+/// A node of the block structure of a YAML document, located by line
+#[derive(Debug)]
+struct Node {
+    line: usize,
+    col: usize,
+    /// Line after the node's last line, leaving out trailing comments
+    end: usize,
+    kind: Kind,
+}
+
+#[derive(Debug)]
+enum Kind {
+    Mapping(Vec<(String, Slot)>),
+    Sequence(Vec<Slot>),
+    Scalar {
+        /// A flow collection like `[a, b]`, rendered the same way again
+        flow: bool,
+    },
+    /// A key without a value
+    Empty,
+}
+
+/// A mapping entry or sequence item: where its key or dash starts, where
+/// it ends, and its value
+#[derive(Debug)]
+struct Slot {
+    line: usize,
+    col: usize,
+    end: usize,
+    value: Node,
+}
+
+struct Document {
+    lines: Vec<String>,
+    /// The line ending of the file, `\r\n` when its first line has one
+    newline: &'static str,
+}
+
+impl Document {
+    fn new(content: &str) -> Self {
+        let crlf = content
+            .find('\n')
+            .is_some_and(|end| content[..end].ends_with('\r'));
+        Self {
+            lines: content.lines().map(str::to_string).collect(),
+            newline: if crlf { "\r\n" } else { "\n" },
+        }
+    }
+
+    fn content(&self) -> String {
+        let mut content = self.lines.join(self.newline);
+        content.push_str(self.newline);
+        content
+    }
+
+    fn update(
+        mut self,
+        target: &Value,
+        normalize: &impl Fn(&str) -> Result<Value>,
+    ) -> Result<String> {
+        for _ in 0..MAX_EDITS {
+            let content = self.content();
+            // Without any entries, the file reads as null
+            let current = match normalize(&content)? {
+                Value::Null => Value::Mapping(Default::default()),
+                current => current,
+            };
+            let Some(edit) = diff(&current, target, &mut Vec::new()) else {
+                return Ok(content);
+            };
+
+            let path = match &edit {
+                Edit::Set(path) => {
+                    self.set(path, target)?;
+                    path
+                }
+                Edit::Remove(path) => {
+                    self.remove(path, target)?;
+                    path
+                }
+            };
+            if self.content() == content {
+                return Err(HookError::ConfigError(format!(
+                    "editing '{}' had no effect",
+                    dotted(path)
+                )));
+            }
+        }
+
+        Err(HookError::ConfigError("Too many changes".into()))
+    }
+
+    fn set(&mut self, path: &[Key], target: &Value) -> Result<()> {
+        let Some(root) = self.root() else {
+            // Nothing but comments, which stay on top
+            if self
+                .lines
+                .last()
+                .is_some_and(|line| !line.trim().is_empty())
+            {
+                self.lines.push(String::new());
+            }
+            self.lines
+                .extend(render(target).lines().map(str::to_string));
+            return Ok(());
+        };
+        if path.is_empty() {
+            return Err(rewrite_error());
+        }
+        let slots = locate(&root, path);
+        let parent = slots.last().map_or(&root, |slot| &slot.value);
+
+        if slots.len() == path.len() {
+            let slot = slots.last().expect("the root is never set by path");
+            self.replace(slot, &path[path.len() - 1], lookup(target, path));
+            return Ok(());
+        }
+
+        let depth = slots.len();
+        let value = lookup(target, &path[..=depth]);
+        match (&parent.kind, &path[depth]) {
+            (Kind::Mapping(_), Key::Field(key)) => {
+                let mut lines = entry_lines(key, value, parent.col, false);
+                if depth == 0 {
+                    // A blank line between sections
+                    lines.insert(0, String::new());
+                }
+                self.lines.splice(parent.end..parent.end, lines);
+            }
+            (Kind::Sequence(items), Key::Index(index)) if *index == items.len() => {
+                let lines = item_lines(value, parent.col, false);
+                self.lines.splice(parent.end..parent.end, lines);
+            }
+            _ if depth == 0 => return Err(rewrite_error()),
+            _ => self.replace(
+                slots[depth - 1],
+                &path[depth - 1],
+                lookup(target, &path[..depth]),
+            ),
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, path: &[Key], target: &Value) -> Result<()> {
+        let Some(root) = self.root() else {
+            return Ok(());
+        };
+        let slots = locate(&root, path);
+        if slots.len() < path.len() {
+            // Inside a flow collection like `[a, b]`, which is rewritten whole
+            if let Some(slot) = slots.last() {
+                if matches!(slot.value.kind, Kind::Scalar { flow: true }) {
+                    let depth = slots.len();
+                    self.replace(slot, &path[depth - 1], lookup(target, &path[..depth]));
+                }
+            }
+            return Ok(());
+        }
+
+        let depth = path.len() - 1;
+        let parent = if depth == 0 {
+            &root
+        } else {
+            &slots[depth - 1].value
+        };
+        let siblings = match &parent.kind {
+            Kind::Mapping(entries) => entries.len(),
+            Kind::Sequence(items) => items.len(),
+            _ => 0,
+        };
+        let slot = &slots[depth];
+
+        // Aliases of an anchor being removed need a copy of its value first
+        for line in slot.line..slot.end {
+            for anchor in anchors(&self.lines[line]) {
+                if let Some(alias) = find_alias(&root, &self.lines, &anchor, &mut Vec::new()) {
+                    return self.set(&alias, target);
+                }
+            }
+        }
+
+        // Something in front of the key, like the dash of a list item
+        let shares_line = self.indent(slot.line) < slot.col && matches!(path[depth], Key::Field(_));
+
+        // The last section goes too, leaving the comments around it
+        if (siblings > 1 || depth == 0) && !shares_line {
+            let (start, end) = (slot.line, slot.end);
+            self.lines.drain(start..end);
+
+            // Don't leave two blank lines where a section was
+            let blank = |line: Option<&String>| line.is_none_or(|line| line.trim().is_empty());
+            if start > 0
+                && start < self.lines.len()
+                && blank(self.lines.get(start - 1))
+                && blank(self.lines.get(start))
+            {
+                self.lines.remove(start);
+            }
+        } else if depth == 0 {
+            return Err(rewrite_error());
+        } else {
+            // Rewrite the parent when it would be left empty or without the
+            // entry on its dash line
+            self.replace(
+                slots[depth - 1],
+                &path[depth - 1],
+                lookup(target, &path[..depth]),
+            );
+        }
+        Ok(())
+    }
+
+    // Replaces an entry or item with `value`, keeping the trailing comment
+    // of a scalar or flow collection on one line
+    fn replace(&mut self, slot: &Slot, key: &Key, value: &Value) {
+        let node = &slot.value;
+        let flow = matches!(node.kind, Kind::Scalar { flow: true });
+        if let (Kind::Scalar { .. }, Some(text)) = (&node.kind, inline(value, flow)) {
+            let line = &self.lines[node.line];
+            let len = match flow {
+                true => flow_len(&line[node.col..]),
+                false => Some(scalar_len(&line[node.col..])),
+            };
+            if let Some(len) = len.filter(|_| node.end == node.line + 1) {
+                let end = node.col + len;
+                self.lines[node.line] = format!("{}{}{}", &line[..node.col], text, &line[end..]);
+                return;
+            }
+        }
+
+        let mut lines = match key {
+            Key::Field(key) => entry_lines(key, value, slot.col, flow),
+            Key::Index(_) => item_lines(value, slot.col, flow),
+        };
+        lines[0] = format!(
+            "{}{}",
+            &self.lines[slot.line][..slot.col],
+            &lines[0][slot.col..]
+        );
+        self.lines.splice(slot.line..slot.end, lines);
+    }
+
+    fn root(&self) -> Option<Node> {
+        let line = self.next_significant(0)?;
+        Some(self.node(line, self.indent(line), None))
+    }
+
+    fn is_significant(&self, line: usize) -> bool {
+        let text = self.lines[line].trim_start();
+        !text.is_empty() && !text.starts_with('#') && text != "---"
+    }
+
+    fn next_significant(&self, from: usize) -> Option<usize> {
+        (from..self.lines.len()).find(|&line| self.is_significant(line))
+    }
+
+    fn indent(&self, line: usize) -> usize {
+        let text = &self.lines[line];
+        text.len() - text.trim_start_matches(' ').len()
+    }
+
+    // The line after the last one indented deeper than `col` following
+    // `line`, which all belong to what starts on `line`
+    fn block_end(&self, line: usize, col: Option<usize>) -> usize {
+        let mut end = line + 1;
+        for next in line + 1..self.lines.len() {
+            if !self.is_significant(next) {
+                continue;
+            }
+            if col.is_some_and(|col| self.indent(next) <= col) {
+                break;
+            }
+            end = next + 1;
+        }
+        end
+    }
+
+    // Parses the node whose content starts at `line` and `col`, owned by a
+    // key or dash at column `owner`
+    fn node(&self, line: usize, col: usize, owner: Option<usize>) -> Node {
+        let text = &self.lines[line][col..];
+        if is_dash(text) {
+            self.sequence(line, col)
+        } else if key_of(text).is_some() {
+            self.mapping(line, col)
+        } else {
+            Node {
+                line,
+                col,
+                end: self.block_end(line, owner),
+                kind: Kind::Scalar {
+                    flow: text.starts_with(['[', '{']),
+                },
+            }
+        }
+    }
+
+    fn mapping(&self, line: usize, col: usize) -> Node {
+        let mut entries = Vec::new();
+        let mut current = line;
+
+        loop {
+            let text = &self.lines[current][col..];
+            let Some((key, after)) = key_of(text) else {
+                break;
+            };
+
+            let value_col = col + after;
+            let rest = strip_properties(&self.lines[current][value_col..]);
+            let value = if !rest.is_empty() {
+                let offset = self.lines[current].len() - rest.len();
+                self.node(current, offset, Some(col))
+            } else {
+                self.block_value(current, col)
+            };
+
+            let end = self.block_end(current, Some(col)).max(value.end);
+            entries.push((
+                key,
+                Slot {
+                    line: current,
+                    col,
+                    end,
+                    value,
+                },
+            ));
+
+            match self.next_significant(end) {
+                Some(next)
+                    if self.indent(next) == col
+                        && !is_dash(&self.lines[next][col..])
+                        && key_of(&self.lines[next][col..]).is_some() =>
+                {
+                    current = next
+                }
+                _ => break,
+            }
+        }
+
+        let end = entries.last().map_or(line + 1, |(_, slot)| slot.end);
+        Node {
+            line,
+            col,
+            end,
+            kind: Kind::Mapping(entries),
+        }
+    }
+
+    // The value of a key with nothing after the colon: an indented block, a
+    // list at the key's own indentation, or nothing
+    fn block_value(&self, line: usize, col: usize) -> Node {
+        match self.next_significant(line + 1) {
+            Some(next) if self.indent(next) > col => self.node(next, self.indent(next), Some(col)),
+            Some(next) if self.indent(next) == col && is_dash(&self.lines[next][col..]) => {
+                self.sequence(next, col)
+            }
+            _ => Node {
+                line,
+                col: self.lines[line].len(),
+                end: line + 1,
+                kind: Kind::Empty,
+            },
+        }
+    }
+
+    fn sequence(&self, line: usize, col: usize) -> Node {
+        let mut items = Vec::new();
+        let mut current = line;
+
+        loop {
+            let after_dash = &self.lines[current][col + 1..];
+            let content = after_dash.trim_start_matches(' ');
+            let value = if content.is_empty() || content.starts_with('#') {
+                self.block_value(current, col)
+            } else {
+                let content_col = self.lines[current].len() - content.len();
+                self.node(current, content_col, Some(col))
+            };
+
+            let end = self.block_end(current, Some(col)).max(value.end);
+            items.push(Slot {
+                line: current,
+                col,
+                end,
+                value,
+            });
+
+            match self.next_significant(end) {
+                Some(next) if self.indent(next) == col && is_dash(&self.lines[next][col..]) => {
+                    current = next
+                }
+                _ => break,
+            }
+        }
+
+        let end = items.last().map_or(line + 1, |slot| slot.end);
+        Node {
+            line,
+            col,
+            end,
+            kind: Kind::Sequence(items),
+        }
+    }
+}
+
+// Changing the type of the root, or removing its only entry when it shares a
+// line with something else, can't be done with a minimal edit
+fn rewrite_error() -> HookError {
+    HookError::ConfigError("the whole document would have to be rewritten".into())
+}
+
+// The slots along `path`, as far as it exists in the document
+fn locate<'a>(root: &'a Node, path: &[Key]) -> Vec<&'a Slot> {
+    let mut slots = Vec::new();
+    let mut node = root;
+
+    for key in path {
+        let slot = match (&node.kind, key) {
+            (Kind::Mapping(entries), Key::Field(key)) => entries
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, slot)| slot),
+            (Kind::Sequence(items), Key::Index(index)) => items.get(*index),
+            _ => None,
+        };
+        let Some(slot) = slot else {
+            break;
+        };
+        slots.push(slot);
+        node = &slot.value;
+    }
+
+    slots
+}
+
+// The path of a node that is an alias of `anchor`
+fn find_alias(
+    node: &Node,
+    lines: &[String],
+    anchor: &str,
+    path: &mut Vec<Key>,
+) -> Option<Vec<Key>> {
+    let children = match &node.kind {
+        Kind::Mapping(entries) => entries
+            .iter()
+            .map(|(key, slot)| (Key::Field(key.clone()), slot))
+            .collect(),
+        Kind::Sequence(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, slot)| (Key::Index(i), slot))
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    for (key, slot) in children {
+        path.push(key);
+        let value = &slot.value;
+        let is_alias = matches!(value.kind, Kind::Scalar { .. })
+            && lines[value.line][value.col..]
+                .strip_prefix('*')
+                .and_then(|alias| alias.split_whitespace().next())
+                == Some(anchor);
+        if is_alias {
+            return Some(path.clone());
+        }
+        if let Some(found) = find_alias(value, lines, anchor, path) {
+            return Some(found);
+        }
+        path.pop();
+    }
+    None
+}
+
+// Names of the anchors defined on a line, like `base` for `key: &base`
+fn anchors(line: &str) -> Vec<String> {
+    line.split_whitespace()
+        .filter_map(|token| token.strip_prefix('&'))
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn lookup<'a>(value: &'a Value, path: &[Key]) -> &'a Value {
+    path.iter().fold(value, |value, key| {
+        let child = match key {
+            Key::Field(key) => value.get(key.as_str()),
+            Key::Index(index) => value.get(index),
+        };
+        child.unwrap_or(&Value::Null)
+    })
+}
+
+// The first edit that brings `current` closer to `target`
+fn diff(current: &Value, target: &Value, path: &mut Vec<Key>) -> Option<Edit> {
+    let at = |path: &Vec<Key>, key: Key| {
+        let mut path = path.clone();
+        path.push(key);
+        path
+    };
+
+    match (current, target) {
+        (Value::Mapping(current), Value::Mapping(target)) => {
+            for (key, value) in target {
+                let name = scalar(key);
+                match current.get(name.as_str()) {
+                    None if !value.is_null() => return Some(Edit::Set(at(path, Key::Field(name)))),
+                    None => {}
+                    Some(existing) => {
+                        path.push(Key::Field(name));
+                        let edit = diff(existing, value, path);
+                        path.pop();
+                        if edit.is_some() {
+                            return edit;
+                        }
+                    }
+                }
+            }
+            current
+                .iter()
+                .find(|(key, value)| !value.is_null() && target.get(*key).is_none())
+                .map(|(key, _)| Edit::Remove(at(path, Key::Field(scalar(key)))))
+        }
+        (Value::Sequence(current), Value::Sequence(target)) => {
+            if current.len() == target.len() {
+                (0..current.len()).find_map(|i| {
+                    path.push(Key::Index(i));
+                    let edit = diff(&current[i], &target[i], path);
+                    path.pop();
+                    edit
+                })
+            } else if target.len() > current.len() && target.starts_with(current) {
+                Some(Edit::Set(at(path, Key::Index(current.len()))))
+            } else if target.len() < current.len() {
+                let index = (0..current.len())
+                    .find(|&i| target.get(i) != Some(&current[i]))
+                    .unwrap_or(target.len());
+                Some(Edit::Remove(at(path, Key::Index(index))))
+            } else {
+                Some(Edit::Set(path.clone()))
+            }
+        }
+        _ if current == target => None,
+        _ if target.is_null() => Some(Edit::Remove(path.clone())),
+        _ => Some(Edit::Set(path.clone())),
+    }
+}
+
+// Values left out of rendered mappings because they equal the default
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Sequence(items) => items.is_empty(),
+        Value::Mapping(mapping) => mapping.iter().all(|(_, value)| is_empty(value)),
+        _ => false,
+    }
+}
+
+fn entry_lines(key: &str, value: &Value, col: usize, flow: bool) -> Vec<String> {
+    let indent = " ".repeat(col);
+    let key = scalar(&Value::String(key.to_string()));
+    match inline(value, flow) {
+        Some(text) => vec![format!("{}{}: {}", indent, key, text)],
+        None => {
+            let mut lines = vec![format!("{}{}:", indent, key)];
+            lines.extend(block_lines(value, col + 2));
+            lines
+        }
+    }
+}
+
+fn item_lines(value: &Value, col: usize, flow: bool) -> Vec<String> {
+    let indent = " ".repeat(col);
+    match inline(value, flow) {
+        Some(text) => vec![format!("{}- {}", indent, text)],
+        None => {
+            // The first line of the block goes on the dash line
+            let mut lines = block_lines(value, col + 2);
+            lines[0] = format!("{}- {}", indent, &lines[0][col + 2..]);
+            lines
+        }
+    }
+}
+
+fn block_lines(value: &Value, col: usize) -> Vec<String> {
+    match value {
+        Value::Mapping(mapping) => mapping
+            .iter()
+            .filter(|(_, value)| !is_empty(value))
+            .flat_map(|(key, value)| entry_lines(&scalar(key), value, col, false))
+            .collect(),
+        Value::Sequence(items) => items
+            .iter()
+            .flat_map(|item| item_lines(item, col, false))
+            .collect(),
+        other => vec![format!("{}{}", " ".repeat(col), scalar(other))],
+    }
+}
+
+// A value that fits on the line of its key or dash: scalars, empty
+// collections and, when `flow` is set, flow collections
+fn inline(value: &Value, flow: bool) -> Option<String> {
+    match value {
+        Value::Mapping(mapping) if mapping.iter().all(|(_, value)| is_empty(value)) => {
+            Some("{}".to_string())
+        }
+        Value::Sequence(items) if items.is_empty() => Some("[]".to_string()),
+        Value::Mapping(mapping) if flow => {
+            let entries = mapping
+                .iter()
+                .filter(|(_, value)| !is_empty(value))
+                .map(|(key, value)| Some(format!("{}: {}", scalar(key), inline(value, true)?)))
+                .collect::<Option<Vec<_>>>()?;
+            Some(format!("{{{}}}", entries.join(", ")))
+        }
+        Value::Sequence(items) if flow => {
+            let items = items
+                .iter()
+                .map(|item| inline(item, true))
+                .collect::<Option<Vec<_>>>()?;
+            Some(format!("[{}]", items.join(", ")))
+        }
+        Value::Mapping(_) | Value::Sequence(_) => None,
+        Value::Tagged(tagged) => inline(&tagged.value, flow),
+        other => Some(scalar(other)),
+    }
+}
+
+fn scalar(value: &Value) -> String {
+    match value {
+        // JSON strings are valid double-quoted YAML, on a single line
+        Value::String(s) if s.contains('\n') => serde_json::to_string(s).unwrap_or_default(),
+        Value::String(s) if s.contains([',', '[', ']', '{', '}']) => {
+            // Quoted so they also work inside flow collections
+            serde_json::to_string(s).unwrap_or_default()
+        }
+        other => serde_yaml::to_string(other)
+            .map(|s| s.trim_end().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+fn is_dash(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+// The key of a `key: value` line and the offset after its colon
+fn key_of(text: &str) -> Option<(String, usize)> {
+    if text.starts_with(['"', '\'']) {
+        let quote = text.chars().next()?;
+        let close = text[1..].find(quote)? + 1;
+        let rest = &text[close + 1..];
+        let colon = rest.len() - rest.trim_start().len();
+        if !rest[colon..].starts_with(':') || !ends_token(&rest[colon + 1..]) {
+            return None;
+        }
+        return Some((text[1..close].to_string(), close + 1 + colon + 1));
+    }
+
+    if text.starts_with([
+        '-', '[', '{', '#', '&', '*', '!', '|', '>', '%', '@', '`', '?',
+    ]) {
+        return None;
+    }
+
+    let mut offset = 0;
+    for part in text.split_inclusive(':') {
+        offset += part.len();
+        if part.contains(" #") {
+            return None;
+        }
+        if part.ends_with(':') && ends_token(&text[offset..]) {
+            let key = text[..offset - 1].trim_end();
+            return (!key.is_empty()).then(|| (key.to_string(), offset));
+        }
+    }
+    None
+}
+
+fn ends_token(rest: &str) -> bool {
+    rest.is_empty() || rest.starts_with([' ', '\t'])
+}
+
+// What follows a key's colon, without anchors, tags and comments
+fn strip_properties(text: &str) -> &str {
+    let mut rest = text.trim_start();
+    while rest.starts_with(['&', '!']) {
+        rest = rest
+            .find(' ')
+            .map_or("", |space| rest[space..].trim_start());
+    }
+    if rest.starts_with('#') {
+        ""
+    } else {
+        rest
+    }
+}
+
+// Length of the scalar at the start of `text`, up to a trailing comment
+fn scalar_len(text: &str) -> usize {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (i, c) in text.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if (c == '"' || c == '\'') && i == 0 => quote = Some(c),
+            None if c == '#' && previous.is_whitespace() => return text[..i].trim_end().len(),
+            None => {}
+        }
+        previous = c;
+    }
+    text.trim_end().len()
+}
+
+// Length of the flow collection at the start of `text`, None if it goes
+// on past the line
+fn flow_len(text: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '[' || c == '{' => depth += 1,
+            None if c == ']' || c == '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            None => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> Value {
+        serde_yaml::from_str(content).unwrap()
+    }
+
+    // Writes `target` over `existing`, checking it reads back as `target`
+    fn edit(existing: &str, target: &str) -> String {
+        let target = parse(target);
+        let written = write(Some(existing), &target, |content| {
+            Ok(serde_yaml::from_str(content)?)
+        })
+        .unwrap();
+        assert_eq!(parse(&written), target);
+        written
+    }
+
+    #[test]
+    fn keeps_comments() {
+        let existing = "\
+# Hooks for this repository
+hooks:
+  pre-commit:
+    - command: cargo fmt # formatting
+      # Not too slow
+      timeout: 30
+
+# Settings
+options:
+  auto_install: true
+";
+        let written = edit(
+            existing,
+            "{hooks: {pre-commit: [{command: cargo fmt, timeout: 60}]}, \
+             options: {auto_install: true}}",
+        );
+        assert_eq!(written, existing.replace("30", "60"));
+    }
+
+    #[test]
+    fn keeps_anchors_and_aliases() {
+        let existing = "\
+hooks:
+  pre-commit:
+    - &fmt
+      command: cargo fmt
+  pre-push:
+    - *fmt
+lint:
+  max_subject_length: 72
+";
+        let written = edit(
+            existing,
+            "{hooks: {pre-commit: [{command: cargo fmt}], pre-push: [{command: cargo fmt}]}, \
+             lint: {max_subject_length: 80}}",
+        );
+        assert_eq!(written, existing.replace("72", "80"));
+    }
+
+    #[test]
+    fn edits_flow_collections() {
+        let existing = "\
+hooks:
+  pre-commit:
+    - command: cargo fmt
+      glob: [\"*.rs\", \"*.toml\"] # sources
+";
+        let written = edit(
+            existing,
+            "{hooks: {pre-commit: [{command: cargo fmt, glob: ['*.rs']}]}}",
+        );
+        assert_eq!(
+            written,
+            existing.replace(r#"["*.rs", "*.toml"]"#, "['*.rs']")
+        );
+    }
+
+    #[test]
+    fn keeps_block_scalars() {
+        let existing = "\
+scripts:
+  check:
+    description: |
+      Runs every check,
+      one after another
+    commands:
+      - command: cargo test
+";
+        let written = edit(
+            existing,
+            "{scripts: {check: {description: \"Runs every check,\\none after another\\n\", \
+             commands: [{command: cargo test --all}]}}}",
+        );
+        assert_eq!(written, existing.replace("cargo test", "cargo test --all"));
+    }
+
+    #[test]
+    fn edits_quoted_keys() {
+        let existing = "\
+hooks:
+  \"pre-commit\":
+    - command: cargo fmt
+  'pre-push':
+    - command: cargo test
+";
+        let written = edit(
+            existing,
+            "{hooks: {pre-commit: [{command: cargo fmt}], pre-push: [{command: cargo nextest}]}}",
+        );
+        assert_eq!(written, existing.replace("cargo test", "cargo nextest"));
+    }
+
+    #[test]
+    fn removes_the_last_item() {
+        let existing = "\
+hooks:
+  pre-commit:
+    - command: cargo fmt
+    # Lints
+    - command: cargo clippy
+options:
+  auto_install: true
+";
+        let written = edit(
+            existing,
+            "{hooks: {pre-commit: [{command: cargo fmt}]}, options: {auto_install: true}}",
+        );
+        assert!(!written.contains("clippy"), "{}", written);
+        assert!(written.starts_with("hooks:\n  pre-commit:\n    - command: cargo fmt\n"));
+        assert!(
+            written.ends_with("options:\n  auto_install: true\n"),
+            "{}",
+            written
+        );
+    }
+
+    #[test]
+    fn adds_and_removes_keys() {
+        let existing = "\
+# Settings
+options:
+  auto_install: true # install on save
+  hooks_dir: .thira
+";
+        let written = edit(
+            existing,
+            "{options: {auto_install: true}, lint: {max_subject_length: 80}}",
+        );
+        assert!(written.starts_with("# Settings\noptions:\n  auto_install: true # install"));
+        assert!(!written.contains("hooks_dir"), "{}", written);
+    }
+
+    #[test]
+    fn keeps_crlf_line_endings() {
+        let existing = "# Settings\r\noptions:\r\n  fail_fast: false # stop early\r\n";
+        let written = edit(existing, "{options: {fail_fast: true, parallel: true}}");
+        assert_eq!(
+            written,
+            "# Settings\r\noptions:\r\n  fail_fast: true # stop early\r\n  parallel: true\r\n"
+        );
+    }
+
+    #[test]
+    fn keeps_comments_around_the_only_section() {
+        // Reads back as null, which counts as empty
+        let existing = "# Settings\n\nlint:\n  types: [feat]\n# The end\n";
+        let written = write(Some(existing), &parse("{}"), |content| {
+            Ok(serde_yaml::from_str(content)?)
+        });
+        assert_eq!(written.unwrap(), "# Settings\n\n# The end\n");
+        assert_eq!(
+            edit("# Settings\n", "{lint: {types: [feat]}}"),
+            "# Settings\n\nlint:\n  types:\n    - feat\n"
+        );
+    }
+
+    #[test]
+    fn never_rewrites_the_whole_file() {
+        let target = parse("[a, b]");
+        let written = write(Some("# Settings\nkey: value\n"), &target, |content| {
+            Ok(serde_yaml::from_str(content)?)
+        });
+        assert!(written.is_err());
+    }
+
+    #[test]
+    fn renders_new_files() {
+        let target = parse("{hooks: {pre-commit: [{command: cargo fmt}]}, lint: {types: [feat]}}");
+        let written = write(None, &target, |content| Ok(serde_yaml::from_str(content)?));
+        assert_eq!(
+            written.unwrap(),
+            "hooks:\n  pre-commit:\n    - command: cargo fmt\n\nlint:\n  types:\n    - feat\n"
+        );
+    }
+
+    #[test]
+    fn parses_dotted_paths() {
+        assert_eq!(
+            parse_dotted("hooks.pre-commit[0].glob[1]"),
+            Some(vec![
+                Key::Field("hooks".into()),
+                Key::Field("pre-commit".into()),
+                Key::Index(0),
+                Key::Field("glob".into()),
+                Key::Index(1),
+            ])
+        );
+        assert_eq!(
+            parse_dotted("lint").map(|path| dotted(&path)),
+            Some("lint".into())
+        );
+        for malformed in ["", "a..b", "a.", "[0]", "a[", "a[x]", "a[0"] {
+            assert_eq!(parse_dotted(malformed), None, "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn locates_values() {
+        let content = "hooks:\n  pre-commit:\n    - command: cargo fmt\n      timeout: 30\n";
+        let path = parse_dotted("hooks.pre-commit[0].timeout").unwrap();
+        assert_eq!(position(content, &path), Some((4, 7)));
+    }
+}
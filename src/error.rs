@@ -1,3 +1,4 @@
+use crate::validate::Problem;
 use std::path::PathBuf;
 use thiserror::Error;
 
@@ -9,14 +10,36 @@ pub enum HookError {
     #[error("Invalid hook name '{0}'. Valid hooks are: pre-commit, commit-msg, etc.")]
     InvalidHook(String),
 
-    // #[error("Hook execution failed: {hook_name} - {reason}")]
-    // HookExecutionError { hook_name: String, reason: String },
+    #[error("Hook execution failed: {hook_name} - {reason}")]
+    HookExecutionError { hook_name: String, reason: String },
+
     #[error("Script execution failed: {script_name} - {reason}")]
     ScriptExecutionError { script_name: String, reason: String },
 
+    #[error("Hook repository {repo}: {reason}")]
+    RepoError { repo: String, reason: String },
+
+    #[error("Failed to set up {language} environment: {reason}")]
+    ToolchainError { language: String, reason: String },
+
+    #[error("Found {0} potential secret(s) in staged changes")]
+    SecretsFound(usize),
+
+    #[error("Staged changes violate {0} commit policy rule(s)")]
+    PolicyViolations(usize),
+
+    #[error("Branch policy violation on '{branch}': {reason}")]
+    BranchPolicyError { branch: String, reason: String },
+
     #[error("Configuration error: {0}")]
     ConfigError(String),
 
+    #[error("Found {} problem(s) in the configuration", .0.len())]
+    InvalidConfig(Vec<Problem>),
+
+    #[error("The change would leave {} problem(s) in the configuration", .0.len())]
+    RejectedChange(Vec<Problem>),
+
     #[error("Failed to read/write file at {path}: {source}")]
     FileError {
         path: PathBuf,
@@ -0,0 +1,67 @@
+use crate::error::{HookError, Result};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::path::Path;
+
+/// Selects the files a hook command applies to from its `glob` and
+/// `exclude` patterns. Patterns without a `/` match the file name in any
+/// directory, like in `.gitignore`; others match the whole path.
+pub struct FileFilter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+}
+
+impl FileFilter {
+    pub fn new(glob: &[String], exclude: &[String]) -> Result<Self> {
+        Ok(Self {
+            include: build(glob)?,
+            exclude: build(exclude)?,
+        })
+    }
+
+    /// Whether any patterns are configured
+    pub fn is_active(&self) -> bool {
+        self.include.is_some() || self.exclude.is_some()
+    }
+
+    pub fn matches(&self, file: &str) -> bool {
+        let included = self.include.as_ref().is_none_or(|set| is_match(set, file));
+        let excluded = self.exclude.as_ref().is_some_and(|set| is_match(set, file));
+        included && !excluded
+    }
+
+    pub fn filter(&self, files: &[String]) -> Vec<String> {
+        files
+            .iter()
+            .filter(|file| self.matches(file))
+            .cloned()
+            .collect()
+    }
+}
+
+fn is_match(set: &GlobSet, file: &str) -> bool {
+    let name = Path::new(file)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    set.is_match(file) || set.is_match(name)
+}
+
+fn build(patterns: &[String]) -> Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| HookError::ConfigError(format!("Invalid glob '{}': {}", pattern, e)))?;
+        builder.add(glob);
+    }
+
+    builder
+        .build()
+        .map(Some)
+        .map_err(|e| HookError::ConfigError(format!("Invalid globs: {}", e)))
+}
@@ -0,0 +1,429 @@
+use crate::error::{HookError, Result};
+use serde::Serialize;
+use serde_yaml::Value;
+use std::path::{Path, PathBuf};
+
+/// File formats the configuration can be kept in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl Format {
+    /// The format of a file, by its extension
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Self::Toml,
+            Some("json") => Self::Json,
+            _ => Self::Yaml,
+        }
+    }
+
+    /// The file `thira config convert` writes by default: `package.json`
+    /// for JSON when the repository has one, else a file of athira's own
+    pub fn file(self) -> PathBuf {
+        let name = match self {
+            Self::Yaml => "hooks.yaml",
+            Self::Toml => "athira.toml",
+            Self::Json if Path::new("package.json").exists() => "package.json",
+            Self::Json => "athira.json",
+        };
+        PathBuf::from(name)
+    }
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Yaml => "YAML",
+            Self::Toml => "TOML",
+            Self::Json => "JSON",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// The tables that may hold the configuration in a file, preferred first.
+// An empty path is the whole file; files shared with other tools keep it in
+// a section of their own.
+fn sections(path: &Path) -> &'static [&'static [&'static str]] {
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+
+    match Format::of(path) {
+        _ if name == "Cargo.toml" => &[
+            &["package", "metadata", "athira"],
+            &["workspace", "metadata", "athira"],
+        ],
+        Format::Toml if name == "athira.toml" => &[&["tool", "athira"], &[]],
+        Format::Toml => &[&["tool", "athira"]],
+        Format::Json if name == "athira.json" => &[&[]],
+        Format::Json => &[&["athira"]],
+        Format::Yaml => &[&[]],
+    }
+}
+
+/// Reads the configuration from a file, None if the file doesn't exist or
+/// has no configuration
+pub fn read(path: &Path) -> Result<Option<Value>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let content = read_content(path)?;
+    let value = parse(path, &content)
+        .map_err(|e| HookError::ConfigError(format!("{}: {}", path.display(), e)))?;
+
+    Ok(section(path, &value)
+        .and_then(|section| lookup(&value, section))
+        .cloned())
+}
+
+/// Whether a file holds a configuration: any file of athira's own, or a
+/// shared file with an athira section
+pub fn has_config(path: &Path) -> bool {
+    path.exists() && (standalone(path) || matches!(read(path), Ok(Some(_))))
+}
+
+/// Where in a file the configuration is kept, like
+/// `Cargo.toml [package.metadata.athira]`
+pub fn describe(path: &Path) -> String {
+    let content = read_content(path).unwrap_or_default();
+    let value = parse(path, &content).unwrap_or(Value::Null);
+
+    match section(path, &value).unwrap_or(sections(path)[0]) {
+        [] => path.display().to_string(),
+        section if Format::of(path) == Format::Toml => {
+            format!("{} [{}]", path.display(), section.join("."))
+        }
+        section => format!("{} \"{}\"", path.display(), section.join(".")),
+    }
+}
+
+/// The content of a TOML or JSON file with `config` written to it, keeping
+/// the rest of a shared file as it is. Unset and empty settings are left
+/// out, and the comments of TOML entries that don't change are kept. YAML
+/// files are rendered in full.
+pub fn write(path: &Path, config: &impl Serialize) -> Result<String> {
+    let error =
+        |e: String| HookError::ConfigError(format!("Failed to write {}: {}", path.display(), e));
+
+    let mut value = serde_json::to_value(config).map_err(|e| error(e.to_string()))?;
+    strip_empty(&mut value);
+
+    let existing = if path.exists() {
+        read_content(path)?
+    } else {
+        String::new()
+    };
+    let parsed = parse(path, &existing).map_err(|e| error(e.clone()))?;
+    let section = section(path, &parsed).unwrap_or_else(|| new_section(path, &parsed));
+
+    match Format::of(path) {
+        Format::Yaml => serde_yaml::to_string(&value).map_err(|e| error(e.to_string())),
+        Format::Toml => {
+            let mut table = toml_edit::ser::to_document(&value)
+                .map_err(|e| error(e.to_string()))?
+                .as_table()
+                .clone();
+            table.set_implicit(true);
+            expand(&mut table);
+
+            let mut document = existing
+                .parse::<toml_edit::DocumentMut>()
+                .map_err(|e| error(e.to_string()))?;
+            let Some((last, parents)) = section.split_last() else {
+                merge(document.as_table_mut(), table);
+                return Ok(document.to_string());
+            };
+
+            let mut current = document.as_table_mut();
+            for key in parents {
+                current = current
+                    .entry(key)
+                    .or_insert_with(|| {
+                        let mut table = toml_edit::Table::new();
+                        table.set_implicit(true);
+                        toml_edit::Item::Table(table)
+                    })
+                    .as_table_mut()
+                    .ok_or_else(|| error(format!("'{}' isn't a table", key)))?;
+            }
+            match current.get_mut(last) {
+                Some(toml_edit::Item::Table(existing)) => merge(existing, table),
+                _ => {
+                    current.insert(last, toml_edit::Item::Table(table));
+                }
+            }
+            Ok(document.to_string())
+        }
+        Format::Json => {
+            let Some((last, parents)) = section.split_last() else {
+                return to_json(&value).map_err(error);
+            };
+
+            let mut document = if existing.trim().is_empty() {
+                serde_json::Value::Object(Default::default())
+            } else {
+                serde_json::from_str(&existing).map_err(|e| error(e.to_string()))?
+            };
+            let mut current = &mut document;
+            for key in parents.iter().chain([last]) {
+                current = current
+                    .as_object_mut()
+                    .ok_or_else(|| error(format!("'{}' isn't inside an object", key)))?
+                    .entry(*key)
+                    .or_insert(serde_json::Value::Null);
+            }
+            *current = value;
+            to_json(&document).map_err(error)
+        }
+    }
+}
+
+/// Takes the configuration out of a file: deletes a file of athira's own,
+/// or only the athira section of a shared one
+pub fn remove(path: &Path) -> Result<()> {
+    match without_config(path)? {
+        Some(content) => replace(path, &content),
+        None => Ok(()),
+    }
+}
+
+/// The content of a file with its configuration taken out, empty when
+/// nothing else is left. None if the file has no configuration.
+pub fn without_config(path: &Path) -> Result<Option<String>> {
+    let content = read_content(path)?;
+    let section = parse(path, &content)
+        .ok()
+        .and_then(|value| section(path, &value));
+    let error =
+        |e: String| HookError::ConfigError(format!("Failed to write {}: {}", path.display(), e));
+
+    let content = match section {
+        Some([]) => String::new(),
+        None if standalone(path) => String::new(),
+        None => return Ok(None),
+        Some(section) => {
+            let (last, parents) = section.split_last().unwrap();
+            match Format::of(path) {
+                Format::Toml => {
+                    let mut document = content
+                        .parse::<toml_edit::DocumentMut>()
+                        .map_err(|e| error(e.to_string()))?;
+                    let mut current = document.as_table_mut() as &mut dyn toml_edit::TableLike;
+                    for key in parents {
+                        // The section may be an inline table, like
+                        // `metadata = { athira = { ... } }`
+                        current = current
+                            .get_mut(key)
+                            .and_then(|item| item.as_table_like_mut())
+                            .ok_or_else(|| error(format!("'{}' isn't a table", key)))?;
+                    }
+                    current.remove(last);
+                    document.to_string()
+                }
+                _ => {
+                    let mut document = serde_json::from_str::<serde_json::Value>(&content)
+                        .map_err(|e| error(e.to_string()))?;
+                    let mut current = &mut document;
+                    for key in parents {
+                        current = &mut current[key];
+                    }
+                    if let Some(object) = current.as_object_mut() {
+                        object.remove(*last);
+                    }
+                    to_json(&document).map_err(error)?
+                }
+            }
+        }
+    };
+    Ok(Some(content))
+}
+
+/// Writes a file, or deletes it when `content` is empty
+pub fn replace(path: &Path, content: &str) -> Result<()> {
+    let result = if content.trim().is_empty() {
+        std::fs::remove_file(path)
+    } else {
+        std::fs::write(path, content)
+    };
+    result.map_err(|e| HookError::FileError {
+        path: path.to_path_buf(),
+        source: e,
+    })
+}
+
+fn read_content(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path).map_err(|e| HookError::FileError {
+        path: path.to_path_buf(),
+        source: e,
+    })
+}
+
+// Whether the file is athira's own, rather than shared with other tools
+fn standalone(path: &Path) -> bool {
+    sections(path).iter().any(|section| section.is_empty())
+}
+
+// The whole file as a YAML value, null when it's empty
+fn parse(path: &Path, content: &str) -> std::result::Result<Value, String> {
+    if content.trim().is_empty() {
+        return Ok(Value::Null);
+    }
+
+    match Format::of(path) {
+        Format::Yaml => serde_yaml::from_str(content).map_err(|e| e.to_string()),
+        Format::Toml => {
+            let table = content
+                .parse::<toml::Table>()
+                .map_err(|e| e.message().to_string())?;
+            serde_yaml::to_value(table).map_err(|e| e.to_string())
+        }
+        Format::Json => {
+            let json =
+                serde_json::from_str::<serde_json::Value>(content).map_err(|e| e.to_string())?;
+            serde_yaml::to_value(json).map_err(|e| e.to_string())
+        }
+    }
+}
+
+// The section of the file holding the configuration, if any does
+fn section(path: &Path, value: &Value) -> Option<&'static [&'static str]> {
+    sections(path)
+        .iter()
+        .find(|section| lookup(value, section).is_some())
+        .copied()
+}
+
+// Where to add the configuration to a file without one: under the first
+// section whose top-level table exists, like `[workspace]` in a virtual
+// manifest, else the first section
+fn new_section(path: &Path, value: &Value) -> &'static [&'static str] {
+    let sections = sections(path);
+    sections
+        .iter()
+        .find(|section| section.first().is_some_and(|key| value.get(key).is_some()))
+        .unwrap_or(&sections[0])
+}
+
+fn lookup<'a>(value: &'a Value, section: &[&str]) -> Option<&'a Value> {
+    section
+        .iter()
+        .try_fold(value, |value, key| value.get(key))
+        .filter(|value| !value.is_null())
+}
+
+// Settings that aren't set are left out rather than written as null, which
+// TOML can't represent, and so are empty lists and tables, as in YAML files
+fn strip_empty(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(object) => {
+            object.values_mut().for_each(strip_empty);
+            object.retain(|_, value| !is_empty(value));
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(strip_empty),
+        _ => {}
+    }
+}
+
+fn is_empty(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Null => true,
+        serde_json::Value::Array(items) => items.is_empty(),
+        serde_json::Value::Object(object) => object.is_empty(),
+        _ => false,
+    }
+}
+
+// Writes `new` over `existing` entry by entry rather than replacing it, so
+// the comments and layout of the entries that stay are kept
+fn merge(existing: &mut toml_edit::Table, new: toml_edit::Table) {
+    use toml_edit::Item;
+
+    existing.retain(|key, _| new.contains_key(key));
+    for (key, item) in new {
+        let item = match (existing.get_mut(&key), item) {
+            (Some(slot), item) if same(slot, &item) => continue,
+            (Some(Item::Table(slot)), Item::Table(table)) => {
+                merge(slot, table);
+                continue;
+            }
+            (Some(Item::ArrayOfTables(slot)), Item::ArrayOfTables(tables)) => {
+                let count = tables.len();
+                for (index, table) in tables.into_iter().enumerate() {
+                    match slot.get_mut(index) {
+                        Some(existing) => merge(existing, table),
+                        None => slot.push(table),
+                    }
+                }
+                while slot.len() > count {
+                    slot.remove(count);
+                }
+                continue;
+            }
+            (Some(Item::Value(slot)), Item::Value(mut value)) => {
+                *value.decor_mut() = slot.decor().clone();
+                *slot = value;
+                continue;
+            }
+            // Written anew when it changes shape, e.g. from an inline array
+            // to an array of tables
+            (_, item) => item,
+        };
+        existing.insert(&key, item);
+    }
+}
+
+// Whether two entries hold the same settings, however they're written
+fn same(a: &toml_edit::Item, b: &toml_edit::Item) -> bool {
+    use serde::de::{Deserialize, IntoDeserializer};
+
+    let plain = |item: &toml_edit::Item| {
+        let value = item.clone().into_value().ok()?;
+        serde_json::Value::deserialize(value.into_deserializer()).ok()
+    };
+    plain(a).is_some() && plain(a) == plain(b)
+}
+
+// Turns the inline tables the serializer writes into regular tables, so
+// each section of the configuration gets a header of its own. Tables with
+// nothing but other tables in them don't get one.
+fn expand(table: &mut toml_edit::Table) {
+    use toml_edit::{ArrayOfTables, Item, Value as TomlValue};
+
+    for (_, item) in table.iter_mut() {
+        *item = match std::mem::take(item) {
+            Item::Value(TomlValue::InlineTable(inline)) if !inline.is_empty() => {
+                let mut table = inline.into_table();
+                table.set_implicit(true);
+                expand(&mut table);
+                Item::Table(table)
+            }
+            Item::Value(TomlValue::Array(array))
+                if !array.is_empty() && array.iter().all(TomlValue::is_inline_table) =>
+            {
+                let mut tables = ArrayOfTables::new();
+                for value in array {
+                    if let TomlValue::InlineTable(inline) = value {
+                        let mut table = inline.into_table();
+                        expand(&mut table);
+                        tables.push(table);
+                    }
+                }
+                Item::ArrayOfTables(tables)
+            }
+            other => other,
+        };
+    }
+}
+
+fn to_json(value: &serde_json::Value) -> std::result::Result<String, String> {
+    serde_json::to_string_pretty(value)
+        .map(|json| json + "\n")
+        .map_err(|e| e.to_string())
+}
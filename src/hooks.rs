@@ -1,11 +1,48 @@
-use crate::config::{CommandConfig, Config};
+use crate::backup::HookBackup;
+use crate::branches::BranchPolicy;
+use crate::builtins::{self, Builtin, Source};
+use crate::conditions;
+use crate::config::{CommandConfig, Config, MigrateExisting};
+use crate::document::Key;
 use crate::error::HookError;
 use crate::error::Result;
-use crate::git::GitRepo;
+use crate::files::FileFilter;
+use crate::formats;
+use crate::git::{hook_spec, is_managed_hook, GitRepo, HookSpec, PushRef, HOOK_MARKER};
+use crate::layers;
 use crate::linter::CommitLinter;
-use std::collections::HashMap;
+use crate::policies::StagedPolicy;
+use crate::repos::{self, RepoSet};
+use crate::runner::{HookRunner, Invocation, PreparedCommand, RunRecord, SkipReason};
+use crate::secrets::{Finding, SecretScanner};
+use crate::template;
+use crate::toolchain::Environment;
+use colored::*;
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io::{IsTerminal, Read};
 use std::path::PathBuf;
 
+/// Which files a hook run operates on, exposed to commands as `${files}`
+#[derive(Debug, Default)]
+pub enum FileSelection {
+    /// Files staged for commit, or for pre-push the files changed by the
+    /// pushed commits
+    #[default]
+    Staged,
+    /// Every file tracked by git
+    All,
+    List(Vec<String>),
+}
+
+#[derive(Debug, Default)]
+pub struct RunOptions {
+    pub files: FileSelection,
+    /// Print the resolved commands instead of executing them
+    pub dry_run: bool,
+}
+
 pub struct HookManager {
     config: Config,
     repo: GitRepo,
@@ -36,32 +73,34 @@ impl HookManager {
     pub fn install_hooks(&self) -> Result<()> {
         self.config.validate()?;
 
-        // Get list of existing hook files
-        let existing_hooks = std::fs::read_dir(&self.repo.hooks_dir)
-            .map_err(|e| HookError::FileError {
-                path: self.repo.hooks_dir.clone(),
-                source: e,
-            })?
-            .filter_map(|entry| entry.ok())
-            .map(|entry| entry.file_name())
-            .filter_map(|name| name.into_string().ok())
-            .collect::<Vec<_>>();
+        // Fetch shared hooks and set up toolchains now rather than on the
+        // first commit
+        for hooks in self.config.hooks.values() {
+            for hook in self.resolve_hooks(hooks)? {
+                if let Some(env) = Environment::for_hook(&hook)? {
+                    env.ensure()?;
+                }
+            }
+        }
+
+        self.backup_existing_hooks()?;
+
+        let names = self.config.hook_names();
 
         // Remove hooks that are no longer in config
-        for hook_name in existing_hooks {
-            if !self.config.hooks.contains_key(&hook_name) {
+        for hook_name in self.repo.hook_files()? {
+            if !names.contains(&hook_name) {
                 self.repo.uninstall_hook(&hook_name)?;
             }
         }
 
         // Clean up old hooks in .git/hooks before installing new ones
-        self.repo
-            .clean_git_hooks(&self.config.hooks.keys().cloned().collect::<Vec<_>>())?;
+        self.repo.clean_git_hooks(&names)?;
 
         // Install new hooks
-        for (name, hooks) in &self.config.hooks {
+        for name in &names {
             self.repo.validate_hook_name(name)?;
-            let script = self.generate_hook_script(hooks);
+            let script = self.generate_hook_script(name);
             self.repo.install_hook(name, &script)?;
         }
 
@@ -73,148 +112,639 @@ impl HookManager {
         Ok(())
     }
 
-    fn generate_hook_script(&self, hooks: &[crate::config::Hook]) -> String {
-        let mut script = String::from("#!/bin/sh\n\n");
+    // Copies hooks that athira didn't write (hand-written, Husky, ...) to
+    // .git/thira/backup/ before they can be overwritten or stop running
+    fn backup_existing_hooks(&self) -> Result<()> {
+        let mut backup = HookBackup::load(&self.repo.git_dir()?)?;
+        let root = std::env::current_dir()?;
+        let git_hooks_dir = GitRepo::get_git_hooks_dir()?;
 
-        for hook in hooks {
-            let mut command = self.substitute_variables(&hook.command);
+        // The directory git currently runs hooks from; a hooks path pointing
+        // anywhere but our directory belongs to someone else
+        let configured = self.repo.configured_hooks_path()?;
+        let active_dir = configured
+            .as_ref()
+            .map(|path| root.join(path))
+            .unwrap_or_else(|| git_hooks_dir.clone());
+        let foreign_path = configured.filter(|path| root.join(path) != self.repo.hooks_dir);
 
-            // Special handling for the commit-msg hook
-            if command.contains("thira") && hook.args.contains(&"commit".to_string()) {
-                // Use the binary path for commit message validation
-                script.push_str(&format!(
-                    "{} commit validate \"$1\"\n",
-                    std::env::current_exe().unwrap().display()
-                ));
-                script.push_str("if [ $? -ne 0 ]; then\n");
-                script.push_str("  exit 1\n");
-                script.push_str("fi\n\n");
-                continue;
+        let mut dirs = vec![self.repo.hooks_dir.clone(), git_hooks_dir];
+        dirs.extend(foreign_path.as_ref().map(|path| root.join(path)));
+        dirs.dedup();
+
+        for name in self.config.hook_names() {
+            for dir in &dirs {
+                let path = dir.join(&name);
+                if !path.is_file() || is_managed_hook(&path) || backup.contains(&path) {
+                    continue;
+                }
+
+                let target = backup.add(&name, &path, *dir == active_dir)?;
+                println!(
+                    "{}",
+                    format!(
+                        "Backed up existing hook {} to {}",
+                        path.display(),
+                        target.display()
+                    )
+                    .yellow()
+                );
             }
+        }
 
-            if !hook.args.is_empty() {
-                let args = hook
-                    .args
-                    .iter()
-                    .map(|arg| self.substitute_variables(arg))
-                    .collect::<Vec<_>>()
-                    .join(" ");
-                command.push_str(&format!(" {}", args));
+        if let Some(path) = foreign_path {
+            if backup.hooks_path.is_none() {
+                println!(
+                    "{}",
+                    format!(
+                        "core.hooksPath was set to {} (another hook manager?); it will be restored on uninstall",
+                        path.display()
+                    )
+                    .yellow()
+                );
+                backup.hooks_path = Some(path);
             }
 
-            script.push_str(&format!("{}\n", command));
-            script.push_str("if [ $? -ne 0 ]; then\n");
-            script.push_str("  exit 1\n");
-            script.push_str("fi\n\n");
+            // Git would keep running the other manager's hooks instead of ours
+            if self.config.options.hooks_dir.contains(".git/hooks") {
+                self.repo.unset_hooks_path()?;
+            }
         }
 
-        script
+        backup.save()
     }
 
-    fn substitute_variables(&self, input: &str) -> String {
-        use regex::Regex;
-        let mut result = input.to_string();
+    fn generate_hook_script(&self, name: &str) -> String {
+        // The hook delegates to athira so that timeouts, retries and failure
+        // policies are applied at run time rather than baked into the script
+        let mut script = format!(
+            "#!/bin/sh\n{}. Changes will be overwritten by 'thira hooks install'.\n\n",
+            HOOK_MARKER
+        );
+        script.push_str(&format!(
+            "exec {} hooks exec {} -- \"$@\"\n",
+            shell_words::quote(&binary_path()),
+            name
+        ));
+        script
+    }
 
-        // Get the current binary path
-        let binary_path = std::env::current_exe()
-            .unwrap_or_else(|_| PathBuf::from("athira"))
-            .display()
-            .to_string();
+    pub fn run_hook(&self, name: &str, args: &[String], options: &RunOptions) -> Result<()> {
+        let spec = hook_spec(name).ok_or_else(|| HookError::InvalidHook(name.to_string()))?;
 
-        // Create regex for variable substitution
-        let re = Regex::new(r"\$\{([^}]+)\}").unwrap();
+        let hooks = self.config.hooks.get(name).map(Vec::as_slice);
+        let enforces_policy = self.config.policy_hooks().contains(&name);
+        if hooks.is_none() && !enforces_policy {
+            if options.dry_run {
+                println!("No commands configured for '{}'", name);
+            }
+            return Ok(());
+        }
 
-        // Collect all replacements first to avoid recursive substitution
-        let mut replacements = Vec::new();
+        if std::env::var("THIRA").is_ok_and(|value| value == "0") {
+            eprintln!(
+                "{}",
+                format!("⚠ THIRA=0 is set, skipping all '{}' commands", name).yellow()
+            );
+            return Ok(());
+        }
+        let hooks = self.resolve_hooks(hooks.unwrap_or_default())?;
 
-        for cap in re.captures_iter(&result) {
-            let var = &cap[0]; // The full match including ${...}
-            let var_name = &cap[1]; // Just the name inside
+        let skip_list = std::env::var("SKIP").unwrap_or_default();
+        let skip_ids = skip_list
+            .split(',')
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .collect::<HashSet<_>>();
 
-            let replacement = match var_name {
-                // Special case for the binary path
-                "athira" => binary_path.clone(),
+        // Git only provides data on stdin for some hooks; read it once so
+        // every command receives its own copy. A terminal means the hook was
+        // started by hand, so there is nothing to read.
+        let stdin = if spec.stdin && !std::io::stdin().is_terminal() {
+            let mut buffer = Vec::new();
+            std::io::stdin().read_to_end(&mut buffer)?;
+            Some(buffer)
+        } else {
+            None
+        };
 
-                // Handle script references
-                s if s.starts_with("scripts.") => {
-                    let script_name = &s["scripts.".len()..];
-                    format!("{} scripts run {}", binary_path, script_name)
-                }
-                s if self.config.scripts.contains_key(s) => {
-                    format!("{} scripts run {}", binary_path, s)
+        // The branch policy guards the git operation itself, so it is checked
+        // before any command runs
+        if !options.dry_run {
+            BranchPolicy::new(&self.config.branches)?.check(name, args, stdin.as_deref())?;
+            if name == "pre-commit" {
+                let violations = self.check_policies()?;
+                if !violations.is_empty() {
+                    for violation in &violations {
+                        println!("{} {}", "✗".red(), violation);
+                    }
+                    return Err(HookError::PolicyViolations(violations.len()));
                 }
+            }
+        }
+
+        if hooks.is_empty() {
+            if options.dry_run {
+                println!("No commands configured for '{}'", name);
+            }
+            return Ok(());
+        }
+
+        let push = if spec.name == "pre-push" {
+            Some(self.push_changes(args, stdin.as_deref())?)
+        } else {
+            None
+        };
+
+        let files = match &options.files {
+            FileSelection::Staged => match &push {
+                Some(push) => push.files.clone(),
+                None => self.repo.staged_files()?,
+            },
+            FileSelection::All => self.repo.tracked_files()?,
+            FileSelection::List(files) => files.clone(),
+        };
+
+        let vars = self.hook_variables(spec, args, &files, push.as_ref());
+        // Builtins check what is about to be committed
+        let source = match (&options.files, &push) {
+            (FileSelection::Staged, None) => Source::Index,
+            _ => Source::WorkTree,
+        };
 
-                // Handle command references (name.N)
-                s if s.contains('.') => {
-                    let parts: Vec<&str> = s.split('.').collect();
-                    match parts[..] {
-                        [script_name, index_str] => {
-                            if let Some(script) = self.config.scripts.get(script_name) {
-                                if let Ok(idx) = index_str.parse::<usize>() {
-                                    if let Some(cmd) = script.commands.get(idx - 1) {
-                                        self.build_command_string(cmd)
-                                    } else {
-                                        var.to_string()
-                                    }
-                                } else {
-                                    var.to_string()
-                                }
-                            } else {
-                                var.to_string()
-                            }
+        // Pre-existing hooks run first when chaining is enabled
+        let existing = if self.config.options.migrate_existing == MigrateExisting::Chain {
+            HookBackup::load(&self.repo.git_dir()?)?
+                .chained(name)
+                .into_iter()
+                .map(|path| crate::config::Hook {
+                    id: Some("existing-hook".to_string()),
+                    command: path.display().to_string(),
+                    ..Default::default()
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let chained = existing.iter().map(|hook| {
+            Ok(PreparedCommand {
+                hook,
+                label: hook_label(hook),
+                invocation: Invocation::Exec(
+                    std::iter::once(hook.command.clone())
+                        .chain(args.iter().cloned())
+                        .collect(),
+                ),
+                env: Vec::new(),
+                skip: (skip_ids.contains(name) || skip_ids.contains("existing-hook"))
+                    .then_some(SkipReason::Requested),
+            })
+        });
+
+        let commands = chained
+            .chain(hooks.iter().map(|hook| {
+                // Commands with glob patterns only see, and only run for,
+                // matching files
+                let filter = FileFilter::new(&hook.glob, &hook.exclude)?;
+                let vars = if filter.is_active() {
+                    let mut filtered = vars.clone();
+                    for key in ["files", "push_files"] {
+                        if let Some(files) = filtered.get_mut(key) {
+                            *files = filter.filter(files);
                         }
-                        [prefix @ "scripts", script_name, index_str] => {
-                            if let Some(script) = self.config.scripts.get(script_name) {
-                                if let Ok(idx) = index_str.parse::<usize>() {
-                                    if script.commands.get(idx - 1).is_some() {
-                                        format!("{} {} run {}", binary_path, prefix, script_name)
-                                    } else {
-                                        var.to_string()
-                                    }
-                                } else {
-                                    var.to_string()
-                                }
-                            } else {
-                                var.to_string()
-                            }
+                    }
+                    Cow::Owned(filtered)
+                } else {
+                    Cow::Borrowed(&vars)
+                };
+
+                let skip = if skip_ids.contains(name)
+                    || hook.id.as_deref().is_some_and(|id| skip_ids.contains(id))
+                {
+                    Some(SkipReason::Requested)
+                } else if let Some(unmet) = hook.when.as_ref().map(conditions::unmet).transpose()? {
+                    unmet.map(SkipReason::Condition)
+                } else if filter.is_active() && vars["files"].is_empty() {
+                    Some(SkipReason::NoMatchingFiles)
+                } else {
+                    None
+                };
+
+                // Environments are only installed when the command will run
+                let env = match Environment::for_hook(hook)? {
+                    Some(env) => {
+                        if !options.dry_run && skip.is_none() {
+                            env.ensure()?;
                         }
-                        _ => var.to_string(),
+                        env.vars()
+                    }
+                    None => Vec::new(),
+                };
+
+                // Skipped commands aren't expanded: one may use what its
+                // condition checks for, like a variable only set in CI
+                let invocation = match skip {
+                    Some(_) => Invocation::Shell(hook.command.clone()),
+                    None => self.resolve_invocation(hook, &vars, source)?,
+                };
+
+                Ok(PreparedCommand {
+                    hook,
+                    label: hook_label(hook),
+                    invocation,
+                    env,
+                    skip,
+                })
+            }))
+            .collect::<Result<Vec<_>>>()?;
+
+        let skipped = |reason: SkipReason| {
+            commands
+                .iter()
+                .filter(|command| command.skip.as_ref() == Some(&reason))
+                .map(|command| command.label.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let requested = skipped(SkipReason::Requested);
+        if !requested.is_empty() {
+            eprintln!("{}", format!("⚠ Skipping via SKIP: {}", requested).yellow());
+        }
+        let unmatched = skipped(SkipReason::NoMatchingFiles);
+        if !unmatched.is_empty() {
+            eprintln!(
+                "{}",
+                format!("Skipping (no matching files): {}", unmatched).dimmed()
+            );
+        }
+        for command in &commands {
+            if let Some(SkipReason::Condition(unmet)) = &command.skip {
+                eprintln!(
+                    "{}",
+                    format!("Skipping {} (when {})", command.label, unmet).dimmed()
+                );
+            }
+        }
+
+        if options.dry_run {
+            print_dry_run(name, args, &commands);
+            return Ok(());
+        }
+
+        let record = HookRunner::new(name, args, stdin.as_deref()).run(&commands)?;
+        record.print_summary();
+
+        // A missing run record shouldn't fail the git operation
+        if let Err(e) = self.repo.git_dir().and_then(|dir| record.save(&dir)) {
+            eprintln!("{}", format!("⚠ Failed to save run record: {}", e).yellow());
+        }
+
+        record.ensure_success()
+    }
+
+    // Replaces hooks that `use` a shared definition with the definition.
+    // Repositories are only checked out when a hook needs them.
+    fn resolve_hooks(&self, hooks: &[crate::config::Hook]) -> Result<Vec<crate::config::Hook>> {
+        if hooks.iter().all(|hook| hook.uses.is_none()) {
+            return Ok(hooks.to_vec());
+        }
+
+        let repos = RepoSet::load(&self.config.repos)?;
+        hooks.iter().map(|hook| repos.resolve(hook)).collect()
+    }
+
+    /// Moves every hook repository to its latest revision and saves the
+    /// config. Returns how many repositories changed.
+    pub fn update_repos(&mut self) -> Result<usize> {
+        let mut updated = 0;
+
+        for repo in &mut self.config.repos {
+            let rev = repos::latest_rev(&repo.repo)?;
+            if rev == repo.rev {
+                println!("  {} is up to date ({})", repo.repo, rev);
+                continue;
+            }
+
+            println!("  {}: {} -> {}", repo.repo, repo.rev, rev.green());
+            repo.rev = rev;
+            updated += 1;
+        }
+
+        if updated > 0 {
+            // Check out the new revisions so a broken update fails here
+            RepoSet::load(&self.config.repos)?;
+            let revs = self.config.repos.clone();
+            self.config.update(|config| {
+                for repo in &mut config.repos {
+                    if let Some(updated) = revs.iter().find(|r| r.repo == repo.repo) {
+                        repo.rev = updated.rev.clone();
                     }
                 }
+            })?;
+        }
+
+        Ok(updated)
+    }
+
+    // The commits and files a push sends, from the refs git passes to
+    // pre-push on stdin
+    fn push_changes(&self, args: &[String], stdin: Option<&[u8]>) -> Result<PushChanges> {
+        let input = String::from_utf8_lossy(stdin.unwrap_or_default());
+        let refs = PushRef::parse_all(&input);
+        let remote = args.first().map(String::as_str);
 
-                // Keep unknown variables as-is
-                _ => var.to_string(),
+        let mut ranges = Vec::new();
+        let mut files = Vec::new();
+        for push in &refs {
+            let Some(range) = self.repo.push_range(push, remote)? else {
+                continue;
             };
+            for file in self.repo.range_files(&range)? {
+                if !files.contains(&file) {
+                    files.push(file);
+                }
+            }
+            ranges.push(range);
+        }
+
+        Ok(PushChanges {
+            refs,
+            ranges,
+            files,
+        })
+    }
+
+    // Named variables for the hook's positional arguments, stdin data and files.
+    // A variable may hold several values, e.g. one per file.
+    fn hook_variables(
+        &self,
+        spec: &HookSpec,
+        args: &[String],
+        files: &[String],
+        push: Option<&PushChanges>,
+    ) -> HashMap<String, Vec<String>> {
+        let mut vars = spec
+            .args
+            .iter()
+            .zip(args)
+            .map(|(name, value)| (name.to_string(), vec![value.clone()]))
+            .collect::<HashMap<_, _>>();
 
-            replacements.push((var.to_string(), replacement));
+        // Positional arguments, for ${1} in commands and $1 or $@ in args
+        for (i, arg) in args.iter().enumerate() {
+            vars.insert((i + 1).to_string(), vec![arg.clone()]);
         }
+        vars.insert("@".to_string(), args.to_vec());
 
-        // Apply all replacements
-        for (var, replacement) in replacements {
-            result = result.replace(&var, &replacement);
+        vars.insert("files".to_string(), files.to_vec());
+
+        if let Some(push) = push {
+            let list =
+                |f: fn(&PushRef) -> &str| push.refs.iter().map(|r| f(r).to_string()).collect();
+            vars.insert("pushed_refs".to_string(), list(|r| &r.local_ref));
+            vars.insert("remote_refs".to_string(), list(|r| &r.remote_ref));
+            vars.insert("push_range".to_string(), push.ranges.clone());
+            vars.insert("push_files".to_string(), push.files.clone());
         }
 
-        result
+        vars
     }
 
-    // Helper method to build a command string
-    fn build_command_string(&self, cmd: &CommandConfig) -> String {
+    fn resolve_invocation(
+        &self,
+        hook: &crate::config::Hook,
+        vars: &HashMap<String, Vec<String>>,
+        source: Source,
+    ) -> Result<Invocation> {
+        let args = hook
+            .args
+            .iter()
+            .map(|arg| self.expand_arg(arg, vars))
+            .collect::<Result<Vec<_>>>()?
+            .concat();
+
+        if let Some(name) = hook.command.strip_prefix(builtins::PREFIX) {
+            return Ok(Invocation::Builtin {
+                check: Builtin::from_name(name.trim())?,
+                args,
+                files: vars.get("files").cloned().unwrap_or_default(),
+                source,
+            });
+        }
+
+        if !hook.shell.unwrap_or(true) {
+            let words = shell_words::split(&hook.command).map_err(|e| {
+                HookError::ConfigError(format!("Invalid command '{}': {}", hook.command, e))
+            })?;
+            let argv = words
+                .iter()
+                .map(|word| self.expand_arg(word, vars))
+                .collect::<Result<Vec<_>>>()?
+                .concat()
+                .into_iter()
+                .chain(args)
+                .collect::<Vec<_>>();
+            return Ok(Invocation::Exec(argv));
+        }
+
+        let mut command = self.substitute_variables(&hook.command, vars)?;
+
+        // Special handling for the commit-msg hook
+        if command.contains("thira") && hook.args.contains(&"commit".to_string()) {
+            // Use the binary path for commit message validation
+            return Ok(Invocation::Shell(format!(
+                "{} commit validate \"$1\"",
+                shell_words::quote(&binary_path())
+            )));
+        }
+
+        if !args.is_empty() {
+            command.push(' ');
+            command.push_str(&shell_words::join(&args));
+        }
+
+        Ok(Invocation::Shell(command))
+    }
+
+    /// Expands a single configured argument into the words passed to the
+    /// command. A variable, `$1` or `$@` making up the whole argument may
+    /// expand to several words; elsewhere values are inserted as-is.
+    fn expand_arg(&self, arg: &str, vars: &HashMap<String, Vec<String>>) -> Result<Vec<String>> {
+        let re = Regex::new(r"\$?\$\{([^}]+)\}|\$(\d+|@)").unwrap();
+        let expand = |caps: &regex::Captures| -> Result<Vec<String>> {
+            if template::is_escaped(caps) {
+                return Ok(vec![caps[0][1..].to_string()]);
+            }
+            let name = caps.get(1).or_else(|| caps.get(2)).unwrap().as_str();
+            match self.lookup_variable(name, vars)? {
+                Some(Expansion::Words(words)) => Ok(words),
+                Some(Expansion::Shell(snippet)) => Ok(vec![snippet]),
+                // Like the shell, a missing positional argument expands to nothing
+                None if name.chars().all(|c| c.is_ascii_digit()) => Ok(Vec::new()),
+                None => Err(unknown_variable(&caps[0], arg)),
+            }
+        };
+
+        if let Some(caps) = re.captures(arg).filter(|caps| caps[0].len() == arg.len()) {
+            return expand(&caps);
+        }
+
+        let mut expanded = String::new();
+        let mut last = 0;
+        for caps in re.captures_iter(arg) {
+            let whole = caps.get(0).unwrap();
+            expanded.push_str(&arg[last..whole.start()]);
+            expanded.push_str(&expand(&caps)?.join(" "));
+            last = whole.end();
+        }
+        expanded.push_str(&arg[last..]);
+        Ok(vec![expanded])
+    }
+
+    /// Replaces `${...}` variables in a shell command line. Values are quoted
+    /// so they are never split or run as shell code.
+    fn substitute_variables(
+        &self,
+        input: &str,
+        vars: &HashMap<String, Vec<String>>,
+    ) -> Result<String> {
+        // Replacements aren't rescanned, so substitution isn't recursive
+        let mut substituted = String::new();
+        let mut last = 0;
+        for caps in template::variable_pattern().captures_iter(input) {
+            let whole = caps.get(0).unwrap();
+            substituted.push_str(&input[last..whole.start()]);
+            last = whole.end();
+
+            if template::is_escaped(&caps) {
+                substituted.push_str(&whole.as_str()[1..]);
+                continue;
+            }
+            match self.lookup_variable(&caps[1], vars)? {
+                Some(Expansion::Words(words)) => substituted.push_str(&shell_words::join(&words)),
+                Some(Expansion::Shell(snippet)) => substituted.push_str(&snippet),
+                None => return Err(unknown_variable(whole.as_str(), input)),
+            }
+        }
+        substituted.push_str(&input[last..]);
+        Ok(substituted)
+    }
+
+    fn lookup_variable(
+        &self,
+        var_name: &str,
+        vars: &HashMap<String, Vec<String>>,
+    ) -> Result<Option<Expansion>> {
+        if let Some(value) = template::lookup(var_name) {
+            return Ok(Some(Expansion::Words(vec![value?])));
+        }
+
+        let binary_path = binary_path();
+        let run_script = |name: &str| {
+            Expansion::Words(vec![
+                binary_path.clone(),
+                "scripts".to_string(),
+                "run".to_string(),
+                name.to_string(),
+            ])
+        };
+
+        let expansion = match var_name {
+            // Special case for the binary path
+            "athira" => Some(Expansion::Words(vec![binary_path.clone()])),
+
+            // Hook arguments, stdin data and files (e.g. ${remote_name})
+            s if vars.contains_key(s) => Some(Expansion::Words(vars[s].clone())),
+
+            // Handle command references (name.N and scripts.name.N)
+            s if s
+                .rsplit_once('.')
+                .is_some_and(|(_, index)| index.parse::<usize>().is_ok()) =>
+            {
+                match self.command_reference(s) {
+                    Some((true, script_name, _)) => Some(run_script(script_name)),
+                    Some((false, _, cmd)) => {
+                        Some(Expansion::Shell(self.build_command_string(cmd)?))
+                    }
+                    None => None,
+                }
+            }
+
+            // Handle script references
+            s if s.starts_with("scripts.") => Some(run_script(&s["scripts.".len()..])),
+            s if self.config.scripts.contains_key(s) => Some(run_script(s)),
+
+            _ => None,
+        };
+        Ok(expansion)
+    }
+
+    // The script command `name.N` or `scripts.name.N` refers to, with the
+    // script's name and whether the reference has the `scripts.` prefix
+    fn command_reference<'a>(
+        &'a self,
+        reference: &'a str,
+    ) -> Option<(bool, &'a str, &'a CommandConfig)> {
+        let (name, index_str) = reference.rsplit_once('.')?;
+        let (prefixed, script_name) = match name.strip_prefix("scripts.") {
+            Some(script_name) => (true, script_name),
+            None => (false, name),
+        };
+        let script = self.config.scripts.get(script_name)?;
+        let idx = index_str.parse::<usize>().ok()?;
+        let cmd = script.commands.get(idx.checked_sub(1)?)?;
+        Some((prefixed, script_name, cmd))
+    }
+
+    // Helper method to build a command string. A command whose condition
+    // doesn't hold is skipped, as `scripts run` does, by the no-op `:`.
+    fn build_command_string(&self, cmd: &CommandConfig) -> Result<String> {
+        if cmd
+            .when
+            .as_ref()
+            .map(conditions::unmet)
+            .transpose()?
+            .flatten()
+            .is_some()
+        {
+            return Ok(":".to_string());
+        }
+        let cmd = template::resolve_command(cmd)?;
         let mut parts = Vec::new();
 
         // Add working directory if specified
         if let Some(dir) = &cmd.working_dir {
-            parts.push(format!("cd {} &&", dir.display()));
+            parts.push(format!(
+                "cd {} &&",
+                shell_words::quote(&dir.display().to_string())
+            ));
         }
 
         // Add environment variables if any
         for (key, value) in &cmd.env {
-            parts.push(format!("{}=\"{}\"", key, value));
+            parts.push(format!("{}={}", key, shell_words::quote(value)));
         }
 
         // Add the main command
         parts.push(cmd.command.clone());
 
-        parts.join(" ")
+        Ok(parts.join(" "))
+    }
+
+    /// Looks for credentials in the lines added by the staged changes
+    pub fn scan_secrets(&self) -> Result<Vec<Finding>> {
+        let scanner = SecretScanner::new(&self.config.secrets)?;
+        Ok(scanner.scan_diff(&self.repo.staged_diff()?))
+    }
+
+    /// Every rule of the `policies` section the staged changes break
+    pub fn check_policies(&self) -> Result<Vec<String>> {
+        StagedPolicy::new(&self.config.policies)?.check(&self.repo)
+    }
+
+    pub fn last_run(&self) -> Result<Option<RunRecord>> {
+        RunRecord::load(&self.repo.git_dir()?)
     }
 
     pub fn get_hooks_path(&self) -> Result<String> {
@@ -239,20 +769,32 @@ impl HookManager {
 
     pub fn uninstall_hooks(&mut self) -> Result<()> {
         // First uninstall all hooks from current location
-        for name in self.config.hooks.keys() {
+        let names = self.config.hook_names();
+        for name in &names {
             self.repo.uninstall_hook(name)?;
         }
 
         // Clean up any hooks in .git/hooks
-        self.repo
-            .clean_git_hooks(&self.config.hooks.keys().cloned().collect::<Vec<_>>())?;
+        self.repo.clean_git_hooks(&names)?;
 
         // Unset Git's core.hooksPath
         self.repo.unset_hooks_path()?;
 
-        // Remove the config file
-        if std::path::Path::new("hooks.yaml").exists() {
-            std::fs::remove_file("hooks.yaml")?;
+        // Put back the hooks that were there before athira
+        let backup = HookBackup::load(&self.repo.git_dir()?)?;
+        if let Some(path) = &backup.hooks_path {
+            self.repo.restore_hooks_path(path)?;
+            println!("Restored core.hooksPath to {}", path.display());
+        }
+        for path in backup.restore()? {
+            println!("Restored hook {}", path.display());
+        }
+
+        // Remove the configuration, leaving the rest of a shared file like
+        // Cargo.toml alone
+        let config_file = layers::repository_file();
+        if formats::has_config(&config_file) {
+            formats::remove(&config_file)?;
         }
 
         Ok(())
@@ -260,19 +802,18 @@ impl HookManager {
 
     pub fn unset_hooks_path(&mut self) -> Result<()> {
         // Clean up hooks from old location
-        self.repo
-            .clean_git_hooks(&self.config.hooks.keys().cloned().collect::<Vec<_>>())?;
+        self.repo.clean_git_hooks(&self.config.hook_names())?;
 
         // Unset Git's core.hooksPath
         self.repo.unset_hooks_path()?;
 
-        // Update config to use default .git/hooks directory
-        self.config.options.hooks_dir = ".git/hooks".to_string();
-
-        // Save config without auto-installing
+        // Update config to use default .git/hooks directory, saving it
+        // without auto-installing
         let auto_install = self.config.options.auto_install;
-        self.config.options.auto_install = false;
-        self.config.save()?;
+        self.config.update(|config| {
+            config.options.hooks_dir = ".git/hooks".to_string();
+            config.options.auto_install = false;
+        })?;
         self.config.options.auto_install = auto_install;
 
         // Update repo reference
@@ -281,24 +822,370 @@ impl HookManager {
         Ok(())
     }
 
-    pub fn add_hook(&mut self, name: String, command: String, args: Vec<String>) -> Result<()> {
+    pub fn add_hook(
+        &mut self,
+        name: String,
+        id: Option<String>,
+        command: String,
+        args: Vec<String>,
+    ) -> Result<()> {
         let hook = crate::config::Hook {
+            id,
             command,
             args,
-            working_dir: None,
+            ..Default::default()
         };
 
         self.repo.validate_hook_name(&name)?;
 
-        self.config
-            .hooks
-            .entry(name.clone())
-            .or_default()
-            .push(hook);
-
         // Just save - config.save() will handle auto_install
-        self.config.save()?;
+        self.config.update(|config| {
+            config
+                .hooks
+                .entry(name.clone())
+                .or_default()
+                .push(hook.clone());
+        })?;
+
+        Ok(())
+    }
+
+    /// Removes a hook's command, counting from 0 as dotted paths do, or all
+    /// of its commands from the repository's configuration file
+    pub fn remove_hook(&mut self, name: &str, index: Option<usize>) -> Result<()> {
+        let file = layers::repository_file();
+        let count = if formats::has_config(&file) {
+            Config::load_repository()?
+                .hooks
+                .get(name)
+                .map_or(0, Vec::len)
+        } else {
+            0
+        };
+        if count == 0 {
+            return Err(HookError::ConfigError(format!(
+                "{} has no '{}' commands",
+                formats::describe(&file),
+                name
+            )));
+        }
 
+        let mut path = vec![Key::Field("hooks".into()), Key::Field(name.to_string())];
+        match index {
+            Some(index) if index >= count => {
+                return Err(HookError::ConfigError(format!(
+                    "'{}' has {} command(s) in {}, so --index goes from 0 to {}",
+                    name,
+                    count,
+                    formats::describe(&file),
+                    count - 1
+                )))
+            }
+            // The hook goes away with its last command
+            Some(index) if count > 1 => path.push(Key::Index(index)),
+            _ => {}
+        }
+
+        Config::set_value(&path, None)?;
+        self.config = Config::load()?;
         Ok(())
     }
 }
+
+struct PushChanges {
+    refs: Vec<PushRef>,
+    /// One `<base>..<tip>` range per pushed ref
+    ranges: Vec<String>,
+    files: Vec<String>,
+}
+
+/// What a `${...}` variable stands for
+enum Expansion {
+    /// Separate words, quoted when inserted into a shell command line
+    Words(Vec<String>),
+    /// A shell snippet inserted verbatim, e.g. a script command reference
+    Shell(String),
+}
+
+fn binary_path() -> String {
+    std::env::current_exe()
+        .unwrap_or_else(|_| PathBuf::from("athira"))
+        .display()
+        .to_string()
+}
+
+/// Name shown for a hook command in output: its id, the shared hook it
+/// uses, or the command line
+pub fn hook_label(hook: &crate::config::Hook) -> String {
+    match hook.id.as_ref().or(hook.uses.as_ref()) {
+        Some(id) => id.clone(),
+        None => std::iter::once(&hook.command)
+            .chain(&hook.args)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+// Variables describing the refs and files of a pre-push
+const PUSH_VARIABLES: &[&str] = &["pushed_refs", "remote_refs", "push_range", "push_files"];
+
+fn unknown_variable(variable: &str, text: &str) -> HookError {
+    HookError::ConfigError(format!("Unknown variable '{}' in '{}'", variable, text))
+}
+
+/// Why `${name}` can't be expanded in the commands of `hook`, if it can't
+pub fn variable_problem(config: &Config, hook: &str, name: &str) -> Option<String> {
+    if template::is_template(name) {
+        return template::problem(name);
+    }
+
+    let is_hook_variable = ["athira", "@", "files"].contains(&name)
+        || name.parse::<usize>().is_ok()
+        || hook_spec(hook).is_some_and(|spec| spec.args.contains(&name))
+        || (hook == "pre-push" && PUSH_VARIABLES.contains(&name));
+    if is_hook_variable {
+        return None;
+    }
+
+    let reference = name.strip_prefix("scripts.").unwrap_or(name);
+    let (script, index) = match reference.rsplit_once('.') {
+        Some((script, index)) if index.parse::<usize>().is_ok() => {
+            (script, index.parse::<usize>().ok())
+        }
+        _ => (reference, None),
+    };
+
+    match config.scripts.get(script) {
+        Some(found) => index
+            .filter(|&index| index == 0 || index > found.commands.len())
+            .map(|index| format!("script '{}' has no command {}", script, index)),
+        None if reference != name || index.is_some() => {
+            Some(format!("script '{}' doesn't exist", script))
+        }
+        None => Some(template::unsupported(name)),
+    }
+}
+
+fn print_dry_run(name: &str, args: &[String], commands: &[PreparedCommand]) {
+    println!(
+        "{}",
+        format!("Dry run of '{}' ({} commands):", name, commands.len())
+            .blue()
+            .bold()
+    );
+    if !args.is_empty() {
+        println!("  Hook arguments: {}", args.join(" "));
+    }
+
+    for (i, command) in commands.iter().enumerate() {
+        let skipped = match &command.skip {
+            Some(SkipReason::Requested) => format!(" {}", "(skipped via SKIP)".yellow()),
+            Some(SkipReason::NoMatchingFiles) => {
+                format!(" {}", "(skipped, no matching files)".dimmed())
+            }
+            Some(SkipReason::Condition(unmet)) => {
+                format!(" {}", format!("(skipped, when {})", unmet).dimmed())
+            }
+            None => String::new(),
+        };
+        println!("  {}. {}{}", i + 1, command.label.yellow(), skipped);
+        println!("     $ {}", command.invocation);
+        if let Some(dir) = &command.hook.working_dir {
+            println!("     in {}", dir.display());
+        }
+        if let Ok(Some(env)) = Environment::for_hook(command.hook) {
+            let state = if env.is_ready() {
+                ""
+            } else {
+                ", not installed yet"
+            };
+            println!(
+                "     with {} environment {}{}",
+                env.language,
+                env.dir.display(),
+                state
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Condition, Hook, ScriptConfig};
+
+    fn manager(config: Config) -> HookManager {
+        let repo = GitRepo {
+            hooks_dir: PathBuf::new(),
+        };
+        HookManager { config, repo }
+    }
+
+    fn files() -> HashMap<String, Vec<String>> {
+        let files = [
+            "plain.rs",
+            "with space.rs",
+            "café.rs",
+            "it's.rs",
+            "$(touch pwned).rs",
+        ];
+        HashMap::from([(
+            "files".to_string(),
+            files.iter().map(|file| file.to_string()).collect(),
+        )])
+    }
+
+    fn shell(command: &str, args: &[&str]) -> String {
+        let hook = Hook {
+            command: command.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            ..Default::default()
+        };
+        match manager(Config::default())
+            .resolve_invocation(&hook, &files(), Source::WorkTree)
+            .unwrap()
+        {
+            Invocation::Shell(command) => command,
+            other => panic!("expected a shell command, got {}", other),
+        }
+    }
+
+    // Runs the command line and returns the words printf received
+    fn words(command: &str) -> Vec<String> {
+        let output = std::process::Command::new("sh")
+            .args(["-c", command])
+            .current_dir(std::env::temp_dir())
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn files_are_quoted_in_shell_commands() {
+        let expected = files()["files"].clone();
+        assert_eq!(words(&shell("printf '%s\\n' ${files}", &[])), expected);
+        assert_eq!(words(&shell("printf '%s\\n'", &["${files}"])), expected);
+    }
+
+    #[test]
+    fn files_inside_a_word_stay_one_argument() {
+        let command = shell("printf '%s\\n'", &["--files=${files}"]);
+        assert_eq!(
+            words(&command),
+            [format!("--files={}", files()["files"].join(" "))]
+        );
+    }
+
+    #[test]
+    fn files_are_separate_arguments_without_a_shell() {
+        let hook = Hook {
+            command: "rustfmt --check ${files}".to_string(),
+            shell: Some(false),
+            ..Default::default()
+        };
+        let Invocation::Exec(argv) = manager(Config::default())
+            .resolve_invocation(&hook, &files(), Source::WorkTree)
+            .unwrap()
+        else {
+            panic!("expected an exec invocation");
+        };
+        assert_eq!(argv[..2], ["rustfmt", "--check"]);
+        assert_eq!(argv[2..], files()["files"]);
+    }
+
+    #[test]
+    fn references_skip_commands_whose_condition_fails() {
+        let command = |command: &str, when: Condition| CommandConfig {
+            command: command.to_string(),
+            description: None,
+            working_dir: None,
+            env: HashMap::new(),
+            env_file: None,
+            when: Some(when),
+        };
+        let mut config = Config::default();
+        config.scripts.insert(
+            "build".to_string(),
+            ScriptConfig {
+                parallel: false,
+                max_threads: 1,
+                commands: vec![
+                    command(
+                        "cargo build",
+                        Condition {
+                            os: vec![std::env::consts::OS.to_string()],
+                            ..Default::default()
+                        },
+                    ),
+                    command(
+                        "echo ${ATHIRA_TEST_UNSET}",
+                        Condition {
+                            env: vec!["ATHIRA_TEST_UNSET".to_string()],
+                            ..Default::default()
+                        },
+                    ),
+                ],
+            },
+        );
+
+        let hook = Hook {
+            command: "${build.1} && ${build.2} && echo done".to_string(),
+            ..Default::default()
+        };
+        let invocation = manager(config).resolve_invocation(&hook, &files(), Source::WorkTree);
+        match invocation.unwrap() {
+            Invocation::Shell(command) => assert_eq!(command, "cargo build && : && echo done"),
+            other => panic!("expected a shell command, got {}", other),
+        }
+    }
+
+    fn pre_commit(hooks: Vec<Hook>) -> HookManager {
+        manager(Config {
+            hooks: HashMap::from([("pre-commit".to_string(), hooks)]),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn skips_before_expanding() {
+        let unknown_variable = Hook {
+            id: Some("echo".to_string()),
+            command: "echo ${no_such_variable}".to_string(),
+            ..Default::default()
+        };
+        let unknown_repo = Hook {
+            uses: Some("no-such-hook".to_string()),
+            ..Default::default()
+        };
+        let options = RunOptions {
+            files: FileSelection::List(Vec::new()),
+            dry_run: true,
+        };
+
+        let dir = crate::git::tests::scratch_repo("skip");
+        crate::git::tests::in_dir(&dir, || {
+            let run = |hooks: &[Hook], var: &str, value: &str| {
+                std::env::set_var(var, value);
+                let result = pre_commit(hooks.to_vec()).run_hook("pre-commit", &[], &options);
+                std::env::remove_var(var);
+                result
+            };
+            let hooks = [unknown_variable, unknown_repo];
+            assert!(run(&hooks[..1], "SKIP", "echo").is_ok());
+            assert!(run(&hooks[..1], "SKIP", "other").is_err());
+            assert!(run(&hooks, "THIRA", "0").is_ok());
+            assert!(run(&hooks[1..], "THIRA", "1").is_err());
+        });
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn escaped_variables_are_left_alone() {
+        assert_eq!(shell("echo $${files}", &[]), "echo ${files}");
+    }
+}
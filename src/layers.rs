@@ -0,0 +1,399 @@
+use crate::error::{HookError, Result};
+use crate::formats;
+use crate::presets;
+use serde_yaml::{Mapping, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Files the repository's configuration may be kept in, in order of
+/// preference. `Cargo.toml` and `package.json` only count when they have an
+/// athira section.
+pub const REPOSITORY_FILES: &[&str] = &[
+    "hooks.yaml",
+    ".thira.yaml",
+    "athira.toml",
+    "athira.json",
+    "Cargo.toml",
+    "package.json",
+];
+pub const LOCAL_FILE: &str = "hooks.local.yaml";
+/// Environment variable naming the repository's configuration file, also
+/// set by `--config`
+pub const CONFIG_ENV: &str = "THIRA_CONFIG";
+
+// Lists that later files add to instead of replacing, with the field that
+// identifies an entry a later file overrides
+const APPENDED_LISTS: &[(&str, Option<&str>)] = &[
+    ("hooks.*", Some("id")),
+    ("repos", Some("repo")),
+    ("secrets.rules", Some("id")),
+    ("policies.companions", None),
+];
+
+/// A file contributing to the effective configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// Built-in defaults, used when the repository has no hooks.yaml
+    Default,
+    /// The user's config.yaml, shared by all repositories
+    User(PathBuf),
+    /// The repository's hooks.yaml, or one of the other `REPOSITORY_FILES`
+    Repository,
+    /// The untracked hooks.local.yaml with personal overrides
+    Local,
+    /// A file another one `extends`
+    Base(PathBuf),
+    /// A configuration built into athira, like `athira:rust`
+    Preset(String),
+}
+
+impl Source {
+    pub fn path(&self) -> Option<PathBuf> {
+        match self {
+            Self::Default => None,
+            Self::User(path) => Some(path.clone()),
+            Self::Repository => Some(repository_file()),
+            Self::Local => Some(PathBuf::from(LOCAL_FILE)),
+            Self::Base(path) => Some(path.clone()),
+            Self::Preset(_) => None,
+        }
+    }
+
+    pub fn read(&self) -> Result<Option<Value>> {
+        if let Self::Preset(name) = self {
+            let content = presets::find(name).unwrap_or_default();
+            return Ok(Some(serde_yaml::from_str(content)?));
+        }
+
+        match self.path() {
+            Some(path) => read_file(&path),
+            None => Ok(None),
+        }
+    }
+
+    // Resolves an entry of this file's `extends`: a preset, or a path
+    // relative to this file, falling back to the user config directory
+    fn extended(&self, name: &str) -> Result<Source> {
+        if let Some(preset) = name.strip_prefix(presets::PREFIX) {
+            if presets::find(preset).is_none() {
+                return Err(HookError::ConfigError(format!(
+                    "{} extends unknown preset '{}'. Available presets: {}",
+                    self,
+                    name,
+                    presets::names().join(", ")
+                )));
+            }
+            return Ok(Self::Preset(preset.to_string()));
+        }
+
+        let path = match name.strip_prefix("~/") {
+            Some(rest) => dirs::home_dir().unwrap_or_default().join(rest),
+            None => PathBuf::from(name),
+        };
+        let dir = self
+            .path()
+            .and_then(|file| file.parent().map(Path::to_path_buf))
+            .unwrap_or_default();
+
+        let candidates = [Some(dir.join(&path)), user_dir().map(|dir| dir.join(&path))];
+        candidates
+            .into_iter()
+            .flatten()
+            .find(|candidate| candidate.exists())
+            .map(Self::Base)
+            .ok_or_else(|| {
+                HookError::ConfigError(format!(
+                    "{} extends '{}', which doesn't exist next to it or in the user config \
+                     directory",
+                    self, name
+                ))
+            })
+    }
+}
+
+/// Reads a configuration file in any of the supported formats. None if it
+/// doesn't exist or has no configuration.
+pub fn read_file(path: &Path) -> Result<Option<Value>> {
+    formats::read(path)
+}
+
+/// The repository's configuration file: the one named by `THIRA_CONFIG`,
+/// else the first of `REPOSITORY_FILES` at the top level of the repository
+/// with a configuration, else hooks.yaml
+pub fn repository_file() -> PathBuf {
+    if let Some(path) = std::env::var_os(CONFIG_ENV).filter(|path| !path.is_empty()) {
+        return PathBuf::from(path);
+    }
+
+    REPOSITORY_FILES
+        .iter()
+        .map(PathBuf::from)
+        .find(|path| formats::has_config(path))
+        .unwrap_or_else(|| PathBuf::from(REPOSITORY_FILES[0]))
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self, self.path()) {
+            (Self::Preset(name), _) => write!(f, "{}{}", presets::PREFIX, name),
+            (_, Some(path)) => write!(f, "{}", path.display()),
+            (_, None) => write!(f, "default"),
+        }
+    }
+}
+
+/// The configuration files merged in order of precedence: the user's
+/// config.yaml, then the repository's file, then hooks.local.yaml, each
+/// after the files it extends. Mappings merge key by key, and a later
+/// file's value replaces an earlier one, except for the lists in
+/// `APPENDED_LISTS`.
+pub struct Layers {
+    pub value: Value,
+    origins: HashMap<String, Source>,
+}
+
+impl Layers {
+    /// Loads every configuration file that exists, starting from `defaults`
+    /// when the repository has no configuration of its own
+    pub fn load(defaults: Value) -> Result<Self> {
+        let mut layers = Self {
+            value: Value::Mapping(Mapping::new()),
+            origins: HashMap::new(),
+        };
+
+        if !formats::has_config(&repository_file()) {
+            layers.merge(defaults, &Source::Default);
+        }
+
+        for (source, value) in files()? {
+            layers.merge(value, &source);
+        }
+
+        Ok(layers)
+    }
+
+    fn merge(&mut self, value: Value, source: &Source) {
+        merge(&mut self.value, value, "", source, &mut self.origins);
+    }
+
+    /// The file a value came from, by its dotted path like `lint.types` or
+    /// `hooks.pre-commit[0].command`
+    pub fn origin(&self, path: &str) -> Source {
+        if let Some(source) = self.origins.get(path) {
+            return source.clone();
+        }
+
+        // Fields left out of a list entry still belong to that entry
+        path.rfind(']')
+            .and_then(|end| self.origins.get(&path[..=end]))
+            .cloned()
+            .unwrap_or(Source::Default)
+    }
+}
+
+/// The configuration files, in increasing order of precedence
+pub fn sources() -> Vec<Source> {
+    user_file()
+        .map(Source::User)
+        .into_iter()
+        .chain([Source::Repository, Source::Local])
+        .collect()
+}
+
+/// Every configuration file along with the files and presets it extends,
+/// in the order they're merged: each file after the ones it extends. The
+/// values have `extends` taken out.
+pub fn files() -> Result<Vec<(Source, Value)>> {
+    let mut files = Vec::new();
+    for source in sources() {
+        expand(source, &mut Vec::new(), &mut files)?;
+    }
+    Ok(files)
+}
+
+fn expand(
+    source: Source,
+    chain: &mut Vec<(String, String)>,
+    files: &mut Vec<(Source, Value)>,
+) -> Result<()> {
+    let Some(mut value) = source.read()? else {
+        return Ok(());
+    };
+
+    // Paths are compared as files, so `./hooks.yaml` is `hooks.yaml`
+    let identity = match source.path().and_then(|path| path.canonicalize().ok()) {
+        Some(path) => path.display().to_string(),
+        None => source.to_string(),
+    };
+    if let Some(start) = chain.iter().position(|(file, _)| *file == identity) {
+        let cycle: Vec<String> = chain[start..]
+            .iter()
+            .map(|(_, name)| name.clone())
+            .chain([source.to_string()])
+            .collect();
+        return Err(HookError::ConfigError(format!(
+            "Configuration files extend each other: {}",
+            cycle.join(" -> ")
+        )));
+    }
+
+    let extends = value
+        .as_mapping_mut()
+        .and_then(|mapping| mapping.remove("extends"));
+    let names = match extends {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::String(name)) => vec![name],
+        Some(Value::Sequence(items)) => items
+            .iter()
+            .filter_map(|item| item.as_str().map(str::to_string))
+            .collect(),
+        Some(_) => {
+            return Err(HookError::ConfigError(format!(
+                "{}: 'extends' must be a path or preset, or a list of them",
+                source
+            )))
+        }
+    };
+
+    chain.push((identity, source.to_string()));
+    for name in names {
+        let base = source.extended(&name)?;
+        expand(base, chain, files)?;
+    }
+    chain.pop();
+
+    files.push((source, value));
+    Ok(())
+}
+
+/// The user-level configuration file, e.g. `~/.config/athira/config.yaml`
+pub fn user_file() -> Option<PathBuf> {
+    user_dir().map(|dir| dir.join("config.yaml"))
+}
+
+fn user_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("athira"))
+}
+
+fn merge(target: &mut Value, value: Value, path: &str, source: &Source, origins: &mut Origins) {
+    match (target, value) {
+        (Value::Mapping(target), Value::Mapping(entries)) => {
+            for (key, value) in entries {
+                let child = child_path(path, &key);
+                match target.get_mut(&key) {
+                    Some(existing) => merge(existing, value, &child, source, origins),
+                    None => {
+                        record(origins, &child, &value, source);
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Sequence(target), Value::Sequence(items)) if appended(path).is_some() => {
+            let identity = appended(path).flatten();
+            for item in items {
+                let existing = identity.and_then(|field| {
+                    let id = item.get(field)?;
+                    target.iter().position(|entry| entry.get(field) == Some(id))
+                });
+
+                let index = existing.unwrap_or(target.len());
+                record(origins, &format!("{}[{}]", path, index), &item, source);
+                match existing {
+                    Some(index) => target[index] = item,
+                    None => target.push(item),
+                }
+            }
+        }
+        (target, value) => {
+            record(origins, path, &value, source);
+            *target = value;
+        }
+    }
+}
+
+type Origins = HashMap<String, Source>;
+
+// Marks `path` and everything below it as coming from `source`
+fn record(origins: &mut Origins, path: &str, value: &Value, source: &Source) {
+    origins.retain(|key, _| {
+        !key.strip_prefix(path)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(['.', '[']))
+    });
+
+    mark(origins, path, value, source);
+}
+
+fn mark(origins: &mut Origins, path: &str, value: &Value, source: &Source) {
+    origins.insert(path.to_string(), source.clone());
+    match value {
+        Value::Mapping(mapping) => {
+            for (key, value) in mapping {
+                mark(origins, &child_path(path, key), value, source);
+            }
+        }
+        Value::Sequence(items) => {
+            for (i, item) in items.iter().enumerate() {
+                mark(origins, &format!("{}[{}]", path, i), item, source);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Whether later files append to the list at `path`, and if so the field
+// identifying its entries
+fn appended(path: &str) -> Option<Option<&'static str>> {
+    APPENDED_LISTS
+        .iter()
+        .find(|(pattern, _)| match pattern.strip_suffix('*') {
+            Some(prefix) => path
+                .strip_prefix(prefix)
+                .is_some_and(|rest| !rest.is_empty() && !rest.contains(['.', '['])),
+            None => path == *pattern,
+        })
+        .map(|(_, identity)| *identity)
+}
+
+fn child_path(path: &str, key: &Value) -> String {
+    let key = scalar(key);
+    if path.is_empty() {
+        key
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+/// Every scalar in `value` with its dotted path, keys sorted
+pub fn flatten(path: &str, value: &Value) -> Vec<(String, String)> {
+    let mut entries = Vec::new();
+    match value {
+        Value::Mapping(mapping) => {
+            let mut keys = mapping.iter().collect::<Vec<_>>();
+            keys.sort_by_key(|(key, _)| scalar(key));
+            for (key, value) in keys {
+                entries.extend(flatten(&child_path(path, key), value));
+            }
+        }
+        Value::Sequence(items) if !items.is_empty() => {
+            for (i, item) in items.iter().enumerate() {
+                entries.extend(flatten(&format!("{}[{}]", path, i), item));
+            }
+        }
+        Value::Sequence(_) => entries.push((path.to_string(), "[]".to_string())),
+        Value::Tagged(tagged) => entries.extend(flatten(path, &tagged.value)),
+        Value::Null => {}
+        scalar_value => entries.push((path.to_string(), scalar(scalar_value))),
+    }
+    entries
+}
+
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => serde_yaml::to_string(other)
+            .map(|s| s.trim_end().to_string())
+            .unwrap_or_default(),
+    }
+}
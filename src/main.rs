@@ -1,13 +1,33 @@
+mod backup;
+mod branches;
+mod builtins;
+mod conditions;
 mod config;
+mod document;
 mod error;
+mod files;
+mod formats;
 mod git;
 mod hooks;
+mod layers;
 mod linter;
+mod migrate;
+mod policies;
+mod presets;
+mod repos;
+mod runner;
+mod schema;
 mod scripts;
+mod secrets;
+mod template;
+mod templates;
+mod toolchain;
+mod validate;
 
 use clap::{Command, CommandFactory, Parser, Subcommand};
 use colored::*;
 use error::{HookError, Result};
+use std::path::PathBuf;
 use std::process;
 
 #[derive(Parser)]
@@ -18,6 +38,13 @@ use std::process;
     long_about = "A tool for managing git hooks and validating commit messages.\nProvides hooks management, scripts automation, and commit message linting."
 )]
 struct Cli {
+    #[arg(
+        long,
+        global = true,
+        help = "Configuration file to use instead of the repository's hooks.yaml"
+    )]
+    config: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -35,12 +62,39 @@ enum Commands {
     /// Commit message validation commands
     #[command(subcommand)]
     Commit(CommitCommands),
+
+    /// Checks to run from hooks
+    #[command(subcommand)]
+    Check(CheckCommands),
+
+    /// Configuration commands
+    #[command(subcommand)]
+    Config(ConfigCommands),
+
+    /// Commit message rule commands
+    #[command(subcommand)]
+    Lint(LintCommands),
+
+    /// Import hooks and commit rules from husky, pre-commit, lefthook and commitlint
+    Migrate {
+        #[arg(long, help = "Print the resulting configuration without saving it")]
+        dry_run: bool,
+    },
 }
 
 #[derive(Subcommand)]
 enum HooksCommands {
     #[command(about = "Initialize hooks configuration")]
-    Init,
+    Init {
+        #[arg(
+            long,
+            value_enum,
+            help = "Kind of project to configure, detected from its manifest files by default"
+        )]
+        template: Option<templates::Template>,
+        #[arg(long, help = "Replace an existing configuration")]
+        force: bool,
+    },
 
     #[command(about = "Install git hooks")]
     Install,
@@ -52,10 +106,22 @@ enum HooksCommands {
     Add {
         name: String,
         command: String,
+        #[arg(long, help = "Identifier for the command, usable in SKIP")]
+        id: Option<String>,
         #[arg(last = true)]
         args: Vec<String>,
     },
 
+    #[command(about = "Remove a hook, or one of its commands")]
+    Remove {
+        name: String,
+        #[arg(
+            long,
+            help = "Position of the command to remove, from 0 as in hooks.<name>[0]"
+        )]
+        index: Option<usize>,
+    },
+
     #[command(about = "Show current hooks path configuration")]
     ShowPath,
 
@@ -67,6 +133,32 @@ enum HooksCommands {
 
     #[command(about = "List all configured hooks")]
     List,
+
+    #[command(about = "Run a hook's commands without triggering git")]
+    Run {
+        name: String,
+        #[arg(long, help = "Run against every tracked file")]
+        all_files: bool,
+        #[arg(long, num_args = 1.., conflicts_with = "all_files", help = "Run against these files")]
+        files: Vec<String>,
+        #[arg(long, help = "Print the resolved commands without executing them")]
+        dry_run: bool,
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+
+    #[command(about = "Show the result of the last hook run")]
+    Last,
+
+    #[command(about = "Update hook repositories to their latest revision")]
+    Update,
+
+    #[command(about = "Execute the commands configured for a hook", hide = true)]
+    Exec {
+        name: String,
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -93,6 +185,73 @@ enum CommitCommands {
     },
 }
 
+#[derive(Subcommand)]
+enum CheckCommands {
+    #[command(about = "Scan staged changes for credentials")]
+    Secrets,
+    #[command(about = "Check staged changes against the commit policies")]
+    Policies,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    #[command(about = "Show the effective configuration")]
+    Show {
+        #[arg(long, help = "Show the file each value comes from")]
+        origin: bool,
+    },
+
+    #[command(about = "Print one setting of the effective configuration")]
+    Get {
+        #[arg(help = "Dotted path of the setting, e.g. lint.max_subject_length")]
+        path: String,
+    },
+
+    #[command(about = "Change one setting in the repository's configuration file")]
+    Set {
+        #[arg(help = "Dotted path of the setting, e.g. options.auto_install")]
+        path: String,
+        #[arg(help = "New value, read as YAML")]
+        value: String,
+    },
+
+    #[command(about = "Remove one setting from the repository's configuration file")]
+    Unset {
+        #[arg(help = "Dotted path of the setting, e.g. hooks.pre-push")]
+        path: String,
+    },
+
+    #[command(about = "Check the configuration files for problems")]
+    Validate,
+
+    #[command(about = "Print the JSON schema of the configuration file")]
+    Schema,
+
+    #[command(about = "Move the configuration to another file format")]
+    Convert {
+        #[arg(long, value_enum, help = "Format to convert to")]
+        to: formats::Format,
+        #[arg(long, help = "File to write, e.g. Cargo.toml or package.json")]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum LintCommands {
+    /// Commit types allowed in messages
+    #[command(subcommand)]
+    Types(LintTypesCommands),
+}
+
+#[derive(Subcommand)]
+enum LintTypesCommands {
+    #[command(about = "Allow a commit type")]
+    Add { name: String },
+
+    #[command(about = "Stop allowing a commit type")]
+    Remove { name: String },
+}
+
 fn print_error(error: &HookError) {
     println!("\n{}", "ERROR:".red().bold());
     match error {
@@ -112,10 +271,93 @@ fn print_error(error: &HookError) {
         HookError::LintError { kind } => {
             println!("{}", kind);
         }
+        HookError::BranchPolicyError { .. } => {
+            println!("{}", error);
+            println!("\n{}", "SUGGESTIONS:".yellow());
+            println!("1. Work on a feature branch named as the 'branches' section of hooks.yaml requires");
+            println!("2. Rename the current branch with 'git branch -m <new-name>'");
+        }
+        HookError::PolicyViolations(_) => {
+            println!("{}", error);
+            println!("\n{}", "SUGGESTIONS:".yellow());
+            println!("1. Split the change into smaller commits or unstage the listed files");
+            println!("2. Adjust the 'policies' section of hooks.yaml if a rule is too strict");
+        }
+        HookError::InvalidConfig(problems) => {
+            println!("{}", error);
+            for problem in problems {
+                println!("  {} {}", "✗".red(), problem);
+            }
+            println!("\n{}", "SUGGESTIONS:".yellow());
+            println!("1. Fix the listed problems and run 'thira config validate' again");
+            println!("2. Run 'thira config schema' for the full list of settings");
+        }
+        HookError::RejectedChange(problems) => {
+            println!("{}", error);
+            for problem in problems {
+                println!("  {} {}", "✗".red(), problem);
+            }
+            println!("\nNothing was written.");
+            println!("\n{}", "SUGGESTIONS:".yellow());
+            println!("1. Check the setting's path and value");
+            println!("2. Run 'thira config schema' for the full list of settings");
+        }
+        HookError::SecretsFound(_) => {
+            println!("{}", error);
+            println!("\n{}", "SUGGESTIONS:".yellow());
+            println!("1. Remove the secrets and rotate any that were real");
+            println!(
+                "2. Mark false positives with a '{}' comment or add them to the allowlist",
+                secrets::ALLOW_MARKER
+            );
+        }
         _ => println!("{}", error),
     }
 }
 
+// The loaded configuration as a tree, including the lint defaults files
+// leave out
+fn effective_value(config: &config::Config) -> Result<serde_yaml::Value> {
+    let mut value = serde_yaml::to_value(config)?;
+    if let serde_yaml::Value::Mapping(mapping) = &mut value {
+        if !mapping.contains_key("lint") {
+            mapping.insert("lint".into(), serde_yaml::to_value(&config.lint)?);
+        }
+    }
+    Ok(value)
+}
+
+fn parse_path(path: &str) -> Result<Vec<document::Key>> {
+    document::parse_dotted(path).ok_or_else(|| {
+        HookError::ConfigError(format!(
+            "'{}' isn't a setting path, write it like lint.types or hooks.pre-commit[0].command",
+            path
+        ))
+    })
+}
+
+fn same_file(a: &std::path::Path, b: &std::path::Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn format_age(timestamp: u64) -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let seconds = now.saturating_sub(timestamp);
+
+    match seconds {
+        0..=59 => format!("{}s ago", seconds),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        3600..=86399 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
+
 fn print_command_tree() {
     println!("{}", "Available Commands:".blue().bold());
     print_subcommands(&Cli::command(), 0, true);
@@ -173,6 +415,41 @@ fn main() {
 
 fn run() -> Result<()> {
     let cli = Cli::parse();
+
+    // Paths on the command line are relative to where athira was started,
+    // everything else to the top level of the repository, like git hooks
+    let cwd = std::env::current_dir()?;
+    let config = cli
+        .config
+        .clone()
+        .or_else(|| std::env::var_os(layers::CONFIG_ENV).map(PathBuf::from))
+        .filter(|path| !path.as_os_str().is_empty());
+    if let Some(config) = config {
+        // Also passes the file on to athira run from hooks
+        std::env::set_var(layers::CONFIG_ENV, cwd.join(config));
+    }
+    let root = git::GitRepo::toplevel().ok();
+    if let Some(root) = &root {
+        std::env::set_current_dir(root).map_err(|e| HookError::FileError {
+            path: root.clone(),
+            source: e,
+        })?;
+    }
+    let from_root = |path: String| match &root {
+        Some(root) => cwd
+            .join(&path)
+            .strip_prefix(root)
+            .map(|path| path.display().to_string())
+            .unwrap_or(path),
+        None => path,
+    };
+
+    // Configuration commands work even when the configuration is broken
+    let command = match cli.command {
+        Commands::Config(cmd) => return config_command(cmd, &cwd),
+        command => command,
+    };
+
     let mut hook_manager = hooks::HookManager::new().inspect_err(|e| match e {
         HookError::GitNotFound => {
             println!(
@@ -185,9 +462,9 @@ fn run() -> Result<()> {
 
     let mut script_manager = scripts::ScriptManager::new()?;
 
-    match cli.command {
+    match command {
         Commands::Hooks(cmd) => match cmd {
-            HooksCommands::Init => {
+            HooksCommands::Init { template, force } => {
                 let output = std::process::Command::new("git")
                     .args(["rev-parse", "--git-dir"])
                     .output()?;
@@ -200,9 +477,40 @@ fn run() -> Result<()> {
                     return Ok(());
                 }
 
-                let config = config::Config::default();
-                config.save()?;
-                println!("{}", "✓ Hooks configuration initialized.".green());
+                let path = layers::repository_file();
+                if formats::has_config(&path) && !force {
+                    return Err(HookError::ConfigError(format!(
+                        "{} already has a configuration. Run 'thira hooks init --force' to \
+                         replace it",
+                        formats::describe(&path)
+                    )));
+                }
+
+                let template = template.unwrap_or_else(|| match templates::Template::detect() {
+                    Some((template, manifest)) => {
+                        println!("Found {}, using the {} template.", manifest, template);
+                        template
+                    }
+                    None => templates::Template::Minimal,
+                });
+
+                // Start over rather than editing the existing hooks.yaml
+                if force && formats::Format::of(&path) == formats::Format::Yaml && path.exists() {
+                    std::fs::remove_file(&path).map_err(|e| HookError::FileError {
+                        path: path.clone(),
+                        source: e,
+                    })?;
+                }
+
+                template.config()?.save()?;
+                println!(
+                    "{}",
+                    format!(
+                        "✓ Hooks configuration initialized from the {} template.",
+                        template
+                    )
+                    .green()
+                );
                 println!("\n{}", "Next steps:".blue().bold());
                 println!("1. Review the generated hooks.yaml configuration");
                 println!("2. Run 'thira hooks install' to install the hooks");
@@ -219,14 +527,23 @@ fn run() -> Result<()> {
             HooksCommands::Add {
                 name,
                 command,
+                id,
                 args,
             } => {
-                hook_manager.add_hook(name.clone(), command, args)?;
+                hook_manager.add_hook(name.clone(), id, command, args)?;
                 println!(
                     "{}",
                     format!("✓ Hook '{}' added successfully.", name).green()
                 );
             }
+            HooksCommands::Remove { name, index } => {
+                hook_manager.remove_hook(&name, index)?;
+                let removed = match index {
+                    Some(index) => format!("✓ Removed hooks.{}[{}].", name, index),
+                    None => format!("✓ Hook '{}' removed successfully.", name),
+                };
+                println!("{}", removed.green());
+            }
             HooksCommands::ShowPath => {
                 let path = hook_manager.get_hooks_path()?;
                 println!("Current hooks path: {}", path);
@@ -253,11 +570,69 @@ fn run() -> Result<()> {
                             } else {
                                 format!(" {}", hook.args.join(" "))
                             };
-                            println!("    - {}{}", hook.command, args_str);
+                            let id_str = hook
+                                .id
+                                .as_ref()
+                                .map(|id| format!(" [{}]", id.cyan()))
+                                .unwrap_or_default();
+                            let command = match &hook.uses {
+                                Some(uses) => format!("uses {}", uses),
+                                None => hook.command.clone(),
+                            };
+                            println!("    - {}{}{}", command, args_str, id_str);
                         }
                     }
                 }
             }
+            HooksCommands::Last => match hook_manager.last_run()? {
+                Some(record) => {
+                    let args_str = if record.args.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" {}", record.args.join(" "))
+                    };
+                    println!(
+                        "{} {}{} ({})",
+                        "Last hook run:".blue().bold(),
+                        record.hook.yellow(),
+                        args_str,
+                        format_age(record.started_at)
+                    );
+                    record.print_summary();
+                }
+                None => println!("No hook runs recorded yet"),
+            },
+            HooksCommands::Update => {
+                let updated = hook_manager.update_repos()?;
+                if updated == 0 {
+                    println!("{}", "✓ Hook repositories are up to date.".green());
+                } else {
+                    println!(
+                        "{}",
+                        format!("✓ Updated {} hook repositories.", updated).green()
+                    );
+                }
+            }
+            HooksCommands::Run {
+                name,
+                all_files,
+                files,
+                dry_run,
+                args,
+            } => {
+                let files = if all_files {
+                    hooks::FileSelection::All
+                } else if !files.is_empty() {
+                    hooks::FileSelection::List(files.into_iter().map(from_root).collect())
+                } else {
+                    hooks::FileSelection::Staged
+                };
+                let options = hooks::RunOptions { files, dry_run };
+                hook_manager.run_hook(&name, &args, &options)?;
+            }
+            HooksCommands::Exec { name, args } => {
+                hook_manager.run_hook(&name, &args, &hooks::RunOptions::default())?;
+            }
         },
 
         Commands::Scripts(cmd) => match cmd {
@@ -334,6 +709,7 @@ fn run() -> Result<()> {
 
         Commands::Commit(cmd) => match cmd {
             CommitCommands::Validate { message_file } => {
+                let message_file = cwd.join(message_file).display().to_string();
                 match hook_manager.validate_commit_message(&message_file) {
                     Ok(_) => {
                         println!("{}", "✓ Commit message is valid.".green());
@@ -347,6 +723,245 @@ fn run() -> Result<()> {
                 }
             }
         },
+
+        Commands::Lint(LintCommands::Types(cmd)) => match cmd {
+            LintTypesCommands::Add { name } => {
+                config::Config::load()?.add_lint_type(&name)?;
+                println!("{}", format!("✓ Commit type '{}' added.", name).green());
+            }
+            LintTypesCommands::Remove { name } => {
+                config::Config::load()?.remove_lint_type(&name)?;
+                println!("{}", format!("✓ Commit type '{}' removed.", name).green());
+            }
+        },
+
+        Commands::Migrate { dry_run } => {
+            let migration = migrate::Migration::detect()?;
+            if migration.is_empty() {
+                println!(
+                    "{}",
+                    "Found no husky, pre-commit, lefthook or commitlint configuration.".yellow()
+                );
+                return Ok(());
+            }
+
+            // A new configuration only gets the migrated hooks
+            let mut config = if formats::has_config(&layers::repository_file()) {
+                config::Config::load_repository()?
+            } else {
+                migrate::Migration::new_config()
+            };
+            let added = migration.apply(&mut config);
+
+            println!("Read {}", migration.sources.join(", "));
+            for (name, hook) in &migration.hooks {
+                // Only the first line of a script
+                let label = hooks::hook_label(hook);
+                let mut lines = label.lines();
+                let first = lines.next().unwrap_or_default();
+                let more = if lines.next().is_some() { " ..." } else { "" };
+                println!("  {} {}: {}{}", "+".green(), name.blue(), first, more);
+            }
+            for rule in migration.lint.describe() {
+                println!("  {} lint.{}", "+".green(), rule);
+            }
+            if !migration.untranslated.is_empty() {
+                println!("\n{}", "Not translated:".yellow());
+                for message in &migration.untranslated {
+                    println!("  {} {}", "!".yellow(), message);
+                }
+            }
+
+            let path = layers::repository_file();
+            if dry_run {
+                println!("\n{}", config.render(&path)?);
+                return Ok(());
+            }
+
+            // Not installed yet, the old hook manager still owns the hooks
+            config.write_to(&path)?;
+            println!(
+                "\n{} Added {} command(s) to {}",
+                "✓".green(),
+                added,
+                path.display()
+            );
+            println!("\n{}", "Next steps:".blue().bold());
+            println!("1. Review the configuration and the items that weren't translated");
+            println!("2. Run 'thira hooks install' to install the hooks");
+            println!("3. Remove the old tool's configuration and uninstall it");
+        }
+
+        Commands::Check(cmd) => match cmd {
+            CheckCommands::Secrets => {
+                let findings = hook_manager.scan_secrets()?;
+                if !findings.is_empty() {
+                    for finding in &findings {
+                        println!(
+                            "{}:{}: {} ({})",
+                            finding.file,
+                            finding.line,
+                            finding.rule.red(),
+                            finding.redacted
+                        );
+                    }
+                    return Err(HookError::SecretsFound(findings.len()));
+                }
+                println!("{}", "✓ No secrets found in staged changes.".green());
+            }
+            CheckCommands::Policies => {
+                let violations = hook_manager.check_policies()?;
+                if !violations.is_empty() {
+                    for violation in &violations {
+                        println!("{} {}", "✗".red(), violation);
+                    }
+                    return Err(HookError::PolicyViolations(violations.len()));
+                }
+                println!("{}", "✓ Staged changes follow the commit policies.".green());
+            }
+        },
+
+        Commands::Config(_) => unreachable!("handled before loading the configuration"),
+    }
+
+    Ok(())
+}
+
+fn config_command(cmd: ConfigCommands, cwd: &std::path::Path) -> Result<()> {
+    match cmd {
+        ConfigCommands::Show { origin } => {
+            let (config, layers) = config::Config::load_layers()?;
+            let value = effective_value(&config)?;
+            let entries = layers::flatten("", &value);
+            let width = entries
+                .iter()
+                .map(|(path, _)| path.len())
+                .max()
+                .unwrap_or(0);
+
+            for (path, value) in &entries {
+                if origin {
+                    println!(
+                        "{:width$}  {}  {}",
+                        path.blue(),
+                        value,
+                        format!("({})", layers.origin(path)).dimmed(),
+                        width = width
+                    );
+                } else {
+                    println!("{:width$}  {}", path.blue(), value, width = width);
+                }
+            }
+        }
+
+        ConfigCommands::Get { path } => {
+            let config = config::Config::load()?;
+            let value = effective_value(&config)?;
+            let found = document::value_at(&value, &parse_path(&path)?)
+                .filter(|value| !value.is_null())
+                .ok_or_else(|| HookError::ConfigError(format!("'{}' isn't set", path)))?;
+
+            // A single value prints alone, a section like `config show` does
+            let entries = layers::flatten(&path, found);
+            match entries.as_slice() {
+                [(entry, value)] if *entry == path => println!("{}", value),
+                _ => {
+                    let width = entries
+                        .iter()
+                        .map(|(path, _)| path.len())
+                        .max()
+                        .unwrap_or(0);
+                    for (path, value) in &entries {
+                        println!("{:width$}  {}", path.blue(), value, width = width);
+                    }
+                }
+            }
+        }
+
+        ConfigCommands::Set { path, value } => {
+            // Anything that isn't valid YAML is taken as a plain string
+            let value = serde_yaml::from_str(&value).unwrap_or(serde_yaml::Value::String(value));
+            config::Config::set_value(&parse_path(&path)?, Some(value))?;
+            println!("{}", format!("✓ Set {}.", path).green());
+        }
+
+        ConfigCommands::Unset { path } => {
+            config::Config::set_value(&parse_path(&path)?, None)?;
+            println!("{}", format!("✓ Unset {}.", path).green());
+        }
+
+        ConfigCommands::Validate => {
+            let problems = validate::check()?;
+            if !problems.is_empty() {
+                return Err(HookError::InvalidConfig(problems));
+            }
+            println!("{}", "✓ Configuration is valid.".green());
+        }
+
+        ConfigCommands::Convert { to, output } => {
+            let source = layers::repository_file();
+            if !formats::has_config(&source) {
+                return Err(HookError::ConfigError(
+                    "No configuration to convert. Run 'thira hooks init' first.".to_string(),
+                ));
+            }
+
+            let root = std::env::current_dir()?;
+            let target = match output {
+                Some(path) => {
+                    let path = cwd.join(path);
+                    path.strip_prefix(&root).map(PathBuf::from).unwrap_or(path)
+                }
+                None => to.file(),
+            };
+            if formats::Format::of(&target) != to {
+                return Err(HookError::ConfigError(format!(
+                    "'{}' isn't a {} file",
+                    target.display(),
+                    to
+                )));
+            }
+            if same_file(&source, &target) {
+                return Err(HookError::ConfigError(format!(
+                    "The configuration is already in {}",
+                    target.display()
+                )));
+            }
+
+            let config = config::Config::load_repository()?;
+            let from = formats::describe(&source);
+            // Nothing is written unless both files can be
+            let content = config.render(&target)?;
+            let remaining = formats::without_config(&source)?;
+            formats::replace(&target, &content)?;
+            if let Some(remaining) = remaining {
+                formats::replace(&source, &remaining)?;
+            }
+            println!(
+                "{} Moved the configuration from {} to {}",
+                "✓".green(),
+                from,
+                formats::describe(&target)
+            );
+
+            if std::env::var_os(layers::CONFIG_ENV).is_some() {
+                println!(
+                    "{}",
+                    format!(
+                        "Point --config or {} at {} from now on.",
+                        layers::CONFIG_ENV,
+                        target.display()
+                    )
+                    .yellow()
+                );
+            }
+        }
+
+        ConfigCommands::Schema => {
+            let schema = serde_json::to_string_pretty(&schema::schema())
+                .map_err(|e| HookError::ConfigError(e.to_string()))?;
+            println!("{}", schema);
+        }
     }
 
     Ok(())
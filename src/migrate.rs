@@ -0,0 +1,839 @@
+use crate::builtins::{self, Builtin};
+use crate::config::{Config, Hook, Language, LinterConfig};
+use crate::error::{HookError, Result};
+use crate::git::hook_spec;
+use regex::Regex;
+use serde_yaml::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+const HUSKY_DIR: &str = ".husky";
+const PRE_COMMIT_FILE: &str = ".pre-commit-config.yaml";
+const LEFTHOOK_FILES: &[&str] = &[
+    "lefthook.yml",
+    "lefthook.yaml",
+    ".lefthook.yml",
+    ".lefthook.yaml",
+];
+const COMMITLINT_FILES: &[&str] = &[
+    ".commitlintrc",
+    ".commitlintrc.json",
+    ".commitlintrc.yaml",
+    ".commitlintrc.yml",
+    ".commitlintrc.js",
+    ".commitlintrc.cjs",
+    "commitlint.config.js",
+    "commitlint.config.cjs",
+    "commitlint.config.mjs",
+];
+
+// The repository whose hooks athira has builtins for
+const PRE_COMMIT_HOOKS_REPO: &str = "https://github.com/pre-commit/pre-commit-hooks";
+
+// Types allowed by @commitlint/config-conventional
+const CONVENTIONAL_TYPES: &[&str] = &[
+    "build", "chore", "ci", "docs", "feat", "fix", "perf", "refactor", "revert", "style", "test",
+];
+
+// File patterns of pre-commit's `types`. `file` and `text` match every file
+// athira passes to a hook.
+const FILE_TYPES: &[(&str, &[&str])] = &[
+    ("c", &["*.c", "*.h"]),
+    ("c++", &["*.cpp", "*.cc", "*.cxx", "*.hpp"]),
+    ("css", &["*.css"]),
+    ("go", &["*.go"]),
+    ("html", &["*.html", "*.htm"]),
+    ("java", &["*.java"]),
+    ("javascript", &["*.js", "*.mjs", "*.cjs"]),
+    ("json", &["*.json"]),
+    ("jsx", &["*.jsx"]),
+    ("markdown", &["*.md", "*.markdown"]),
+    ("python", &["*.py", "*.pyi"]),
+    ("ruby", &["*.rb"]),
+    ("rust", &["*.rs"]),
+    ("scss", &["*.scss"]),
+    ("shell", &["*.sh", "*.bash"]),
+    ("sql", &["*.sql"]),
+    ("toml", &["*.toml"]),
+    ("ts", &["*.ts"]),
+    ("tsx", &["*.tsx"]),
+    ("yaml", &["*.yaml", "*.yml"]),
+];
+
+/// Hooks and commit message rules translated from the configuration of
+/// other hook managers and commitlint
+#[derive(Default)]
+pub struct Migration {
+    /// Files that were read
+    pub sources: Vec<String>,
+    /// Translated commands with the hook they run in
+    pub hooks: Vec<(String, Hook)>,
+    pub lint: LintRules,
+    /// Settings that couldn't be translated, each with the file it's from
+    pub untranslated: Vec<String>,
+}
+
+/// Commit message rules taken from commitlint
+#[derive(Default)]
+pub struct LintRules {
+    pub types: Option<Vec<String>>,
+    pub scopes: Option<Vec<String>>,
+    pub min_subject_length: Option<usize>,
+    pub max_subject_length: Option<usize>,
+    pub max_body_line_length: Option<usize>,
+    /// Caveats of settings that were only partly translated, by setting
+    pub notes: HashMap<&'static str, String>,
+}
+
+impl LintRules {
+    pub fn is_empty(&self) -> bool {
+        self.describe().is_empty()
+    }
+
+    /// The rules as `setting: value` lines, with their caveats
+    pub fn describe(&self) -> Vec<String> {
+        let lists = [("types", &self.types), ("scopes", &self.scopes)];
+        let lengths = [
+            ("min_subject_length", self.min_subject_length),
+            ("max_subject_length", self.max_subject_length),
+            ("max_body_line_length", self.max_body_line_length),
+        ];
+
+        lists
+            .into_iter()
+            .filter_map(|(name, list)| Some((name, list.as_ref()?.join(", "))))
+            .chain(
+                lengths
+                    .into_iter()
+                    .filter_map(|(name, length)| Some((name, length?.to_string()))),
+            )
+            .map(|(name, value)| match self.notes.get(name) {
+                Some(note) => format!("{}: {} ({})", name, value, note),
+                None => format!("{}: {}", name, value),
+            })
+            .collect()
+    }
+
+    fn apply(&self, lint: &mut LinterConfig) {
+        if let Some(types) = &self.types {
+            lint.types = types.clone();
+        }
+        if let Some(scopes) = &self.scopes {
+            lint.scopes = scopes.clone();
+        }
+        if let Some(length) = self.min_subject_length {
+            lint.min_subject_length = length;
+        }
+        if let Some(length) = self.max_subject_length {
+            lint.max_subject_length = length;
+        }
+        if let Some(length) = self.max_body_line_length {
+            lint.max_body_line_length = length;
+        }
+    }
+}
+
+impl Migration {
+    /// Reads the configuration of every supported tool in the repository
+    pub fn detect() -> Result<Self> {
+        let mut migration = Self::default();
+        // First, so calls to commitlint in hooks can be replaced
+        migration.commitlint()?;
+        migration.husky()?;
+        migration.pre_commit()?;
+        migration.lefthook()?;
+        Ok(migration)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// The configuration to migrate into when the repository has none:
+    /// default options without the sample hooks, scripts or lint rules
+    pub fn new_config() -> Config {
+        Config {
+            hooks: HashMap::new(),
+            scripts: HashMap::new(),
+            lint: LinterConfig::default(),
+            ..Config::default()
+        }
+    }
+
+    /// Adds the translated hooks and rules to a configuration, leaving out
+    /// commands it already has. Returns the number of commands added.
+    pub fn apply(&self, config: &mut Config) -> usize {
+        let mut added = 0;
+        for (name, hook) in &self.hooks {
+            let hooks = config.hooks.entry(name.clone()).or_default();
+            let exists = hooks.iter().any(|existing| {
+                (existing.command == hook.command && existing.args == hook.args)
+                    || (hook.id.is_some() && existing.id == hook.id)
+            });
+            if !exists {
+                hooks.push(hook.clone());
+                added += 1;
+            }
+        }
+
+        self.lint.apply(&mut config.lint);
+        added
+    }
+
+    fn skip(&mut self, source: &str, message: impl AsRef<str>) {
+        self.untranslated
+            .push(format!("{}: {}", source, message.as_ref()));
+    }
+
+    // A command from a shell script or a `run:` line. Calls to commitlint
+    // become athira's own check when its rules were translated.
+    fn add_command(&mut self, source: &str, hook_name: &str, id: Option<String>, command: String) {
+        if hook_name == "commit-msg" && command.contains("commitlint") && !self.lint.is_empty() {
+            self.skip(
+                source,
+                format!("'{}' was replaced by 'thira commit validate'", command),
+            );
+            self.hooks.push((
+                hook_name.to_string(),
+                Hook {
+                    command: "${athira}".to_string(),
+                    args: vec!["commit".into(), "validate".into(), "$1".into()],
+                    ..Default::default()
+                },
+            ));
+            return;
+        }
+
+        self.hooks.push((
+            hook_name.to_string(),
+            Hook {
+                id,
+                command,
+                ..Default::default()
+            },
+        ));
+    }
+
+    fn husky(&mut self) -> Result<()> {
+        let dir = Path::new(HUSKY_DIR);
+        if !dir.is_dir() {
+            return Ok(());
+        }
+        self.sources.push(format!("{}/", HUSKY_DIR));
+
+        let mut scripts = std::fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .collect::<Vec<_>>();
+        scripts.sort();
+
+        for path in scripts {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let source = format!("{}/{}", HUSKY_DIR, name);
+            if hook_spec(&name).is_none() {
+                self.skip(&source, "not a git hook");
+                continue;
+            }
+
+            let content = read(&path)?;
+            let lines = script_lines(&content);
+            if lines.iter().any(|line| is_control_flow(line)) {
+                // Keep the script together so its statements still work
+                self.add_command(&source, &name, None, lines.join("\n"));
+            } else {
+                for line in lines {
+                    self.add_command(&source, &name, None, line);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn pre_commit(&mut self) -> Result<()> {
+        let Some(config) = read_yaml(Path::new(PRE_COMMIT_FILE))? else {
+            return Ok(());
+        };
+        let source = PRE_COMMIT_FILE;
+        self.sources.push(source.to_string());
+
+        let default_stages =
+            strings(config.get("default_stages")).unwrap_or_else(|| vec!["pre-commit".into()]);
+        let global_exclude = match config.get("exclude").and_then(Value::as_str) {
+            Some(regex) => match regex_globs(regex) {
+                Some(globs) => globs,
+                None => {
+                    self.skip(
+                        source,
+                        format!("exclude '{}' has no glob equivalent", regex),
+                    );
+                    Vec::new()
+                }
+            },
+            None => Vec::new(),
+        };
+
+        for (key, _) in config.as_mapping().into_iter().flatten() {
+            let key = key.as_str().unwrap_or_default();
+            let ignored = [
+                "repos",
+                "exclude",
+                "default_stages",
+                "default_install_hook_types",
+                "minimum_pre_commit_version",
+                "ci",
+            ];
+            if !ignored.contains(&key) {
+                self.skip(source, format!("'{}' isn't supported", key));
+            }
+        }
+
+        for repo in config
+            .get("repos")
+            .and_then(Value::as_sequence)
+            .into_iter()
+            .flatten()
+        {
+            let url = repo.get("repo").and_then(Value::as_str).unwrap_or_default();
+            for entry in repo
+                .get("hooks")
+                .and_then(Value::as_sequence)
+                .into_iter()
+                .flatten()
+            {
+                let id = entry.get("id").and_then(Value::as_str).unwrap_or_default();
+                let at = format!("{}: hook '{}'", source, id);
+
+                let hook = match url {
+                    "local" => self.local_hook(&at, entry),
+                    "meta" => {
+                        self.skip(&at, "meta hooks aren't supported");
+                        None
+                    }
+                    url if url.trim_end_matches(".git") == PRE_COMMIT_HOOKS_REPO => {
+                        match Builtin::from_name(id) {
+                            Ok(builtin) => Some(Hook {
+                                command: format!("{}{}", builtins::PREFIX, builtin.name()),
+                                ..Default::default()
+                            }),
+                            Err(_) => {
+                                self.skip(&at, format!("no builtin replaces it ({})", url));
+                                None
+                            }
+                        }
+                    }
+                    url => {
+                        self.skip(
+                            &at,
+                            format!(
+                                "hooks from {} have to be translated by hand, or published \
+                                 in an athira-hooks.yaml and used from 'repos'",
+                                url
+                            ),
+                        );
+                        None
+                    }
+                };
+                let Some(mut hook) = hook else {
+                    continue;
+                };
+
+                hook.id = Some(id.to_string());
+                hook.args
+                    .splice(0..0, strings(entry.get("args")).unwrap_or_default());
+                self.pre_commit_files(&at, entry, &mut hook);
+                hook.exclude.extend(global_exclude.iter().cloned());
+
+                let stages = strings(entry.get("stages")).unwrap_or_else(|| default_stages.clone());
+                for stage in stages {
+                    let name = match stage.as_str() {
+                        "commit" => "pre-commit",
+                        "push" => "pre-push",
+                        "merge-commit" => "pre-merge-commit",
+                        stage => stage,
+                    };
+                    if hook_spec(name).is_none() {
+                        self.skip(&at, format!("stage '{}' isn't a git hook", stage));
+                        continue;
+                    }
+
+                    let mut hook = hook.clone();
+                    if name == "commit-msg" {
+                        // pre-commit passes the message file as the file name
+                        for arg in &mut hook.args {
+                            if arg == "${files}" {
+                                *arg = "$1".to_string();
+                            }
+                        }
+                    }
+                    self.hooks.push((name.to_string(), hook));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // A hook of a `repo: local` entry, running its `entry`
+    fn local_hook(&mut self, at: &str, entry: &Value) -> Option<Hook> {
+        let command = entry
+            .get("entry")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let language = match entry.get("language").and_then(Value::as_str) {
+            Some("system") | Some("script") | None => Language::System,
+            Some("node") => Language::Node,
+            Some("python") => Language::Python,
+            Some("rust") => Language::Rust,
+            Some(language) => {
+                self.skip(at, format!("language '{}' isn't supported", language));
+                return None;
+            }
+        };
+
+        let mut hook = Hook {
+            command: command.to_string(),
+            language,
+            additional_dependencies: strings(entry.get("additional_dependencies"))
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+        if entry.get("pass_filenames").and_then(Value::as_bool) != Some(false) {
+            hook.args.push("${files}".to_string());
+        }
+        Some(hook)
+    }
+
+    // Narrows a pre-commit hook down to the files it ran for
+    fn pre_commit_files(&mut self, at: &str, entry: &Value, hook: &mut Hook) {
+        for (key, value) in entry.as_mapping().into_iter().flatten() {
+            let key = key.as_str().unwrap_or_default();
+            match key {
+                "files" | "exclude" => {
+                    let regex = value.as_str().unwrap_or_default();
+                    match regex_globs(regex) {
+                        Some(globs) if key == "files" => hook.glob.extend(globs),
+                        Some(globs) => hook.exclude.extend(globs),
+                        None => {
+                            self.skip(at, format!("{} '{}' has no glob equivalent", key, regex))
+                        }
+                    }
+                }
+                "types" | "types_or" => {
+                    let types = strings(Some(value)).unwrap_or_default();
+                    let types = types
+                        .iter()
+                        .filter(|kind| !["file", "text"].contains(&kind.as_str()))
+                        .collect::<Vec<_>>();
+                    // `types` must all match, which globs can't express
+                    if key == "types" && types.len() > 1 {
+                        self.skip(at, format!("'types' {:?} can't be combined", types));
+                        continue;
+                    }
+                    for kind in types {
+                        match FILE_TYPES.iter().find(|(name, _)| name == kind) {
+                            Some((_, globs)) => {
+                                hook.glob.extend(globs.iter().map(|glob| glob.to_string()))
+                            }
+                            None => self.skip(at, format!("file type '{}' isn't known", kind)),
+                        }
+                    }
+                }
+                "id"
+                | "name"
+                | "entry"
+                | "language"
+                | "args"
+                | "stages"
+                | "pass_filenames"
+                | "additional_dependencies"
+                | "always_run"
+                | "verbose"
+                | "require_serial"
+                | "description"
+                | "alias" => {}
+                key => self.skip(at, format!("'{}' isn't supported", key)),
+            }
+        }
+    }
+
+    fn lefthook(&mut self) -> Result<()> {
+        let Some(path) = LEFTHOOK_FILES
+            .iter()
+            .map(Path::new)
+            .find(|path| path.exists())
+        else {
+            return Ok(());
+        };
+        let Some(config) = read_yaml(path)? else {
+            return Ok(());
+        };
+        let source = path.display().to_string();
+        self.sources.push(source.clone());
+
+        for (key, section) in config.as_mapping().into_iter().flatten() {
+            let name = key.as_str().unwrap_or_default();
+            if hook_spec(name).is_none() {
+                let cosmetic = ["min_version", "colors", "no_tty", "output", "skip_output"];
+                if !cosmetic.contains(&name) {
+                    self.skip(&source, format!("'{}' isn't supported", name));
+                }
+                continue;
+            }
+
+            for (key, value) in section.as_mapping().into_iter().flatten() {
+                let key = key.as_str().unwrap_or_default();
+                let at = format!("{}: {}", source, name);
+                match key {
+                    "commands" => {
+                        for (id, command) in value.as_mapping().into_iter().flatten() {
+                            let id = id.as_str().unwrap_or_default();
+                            let at = format!("{}.commands.{}", at, id);
+                            self.lefthook_command(&at, name, id, command);
+                        }
+                    }
+                    "scripts" => {
+                        for (file, script) in value.as_mapping().into_iter().flatten() {
+                            let file = file.as_str().unwrap_or_default();
+                            let path = format!(".lefthook/{}/{}", name, file);
+                            let command = match script.get("runner").and_then(Value::as_str) {
+                                Some(runner) => format!("{} {}", runner, path),
+                                None => path,
+                            };
+                            self.add_command(&at, name, Some(file.to_string()), command);
+                        }
+                    }
+                    "parallel" if value.as_bool() != Some(true) => {}
+                    key => self.skip(&at, format!("'{}' isn't supported", key)),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn lefthook_command(&mut self, at: &str, hook_name: &str, id: &str, command: &Value) {
+        let Some(run) = command.get("run").and_then(Value::as_str) else {
+            self.skip(at, "has no 'run'");
+            return;
+        };
+
+        // Shell variables like `${HOME}` are escaped so athira leaves them
+        // to the shell
+        let mut run = run.replace("${", "$${");
+        let placeholders = Regex::new(r"(\$\$)?\{([a-z_0-9]+)\}").unwrap();
+        for caps in placeholders.captures_iter(&run.clone()) {
+            if caps.get(1).is_some() {
+                continue;
+            }
+            let replacement = match &caps[2] {
+                "staged_files" => "${files}".to_string(),
+                "push_files" => "${push_files}".to_string(),
+                "0" => "$@".to_string(),
+                "lefthook_job_name" => id.to_string(),
+                n if n.parse::<usize>().is_ok() => format!("${}", n),
+                other => {
+                    self.skip(
+                        at,
+                        format!(
+                            "placeholder '{{{}}}' isn't supported, so it was left out",
+                            other
+                        ),
+                    );
+                    return;
+                }
+            };
+            run = run.replace(&caps[0], &replacement);
+        }
+
+        self.add_command(at, hook_name, Some(id.to_string()), run);
+        let Some((_, hook)) = self.hooks.last_mut() else {
+            return;
+        };
+
+        let mut skipped = Vec::new();
+        for (key, value) in command.as_mapping().into_iter().flatten() {
+            match key.as_str().unwrap_or_default() {
+                "run" => {}
+                "glob" => hook.glob.extend(strings(Some(value)).unwrap_or_default()),
+                "exclude" => match value {
+                    Value::String(regex) => match regex_globs(regex) {
+                        Some(globs) => hook.exclude.extend(globs),
+                        None => skipped.push(format!("exclude '{}' has no glob equivalent", regex)),
+                    },
+                    globs => hook
+                        .exclude
+                        .extend(strings(Some(globs)).unwrap_or_default()),
+                },
+                "root" => hook.working_dir = value.as_str().map(Into::into),
+                key => skipped.push(format!("'{}' isn't supported", key)),
+            }
+        }
+        for message in skipped {
+            self.skip(at, message);
+        }
+    }
+
+    fn commitlint(&mut self) -> Result<()> {
+        let found = COMMITLINT_FILES
+            .iter()
+            .map(Path::new)
+            .find(|path| path.exists());
+        let (source, config) = match found {
+            Some(path) => {
+                let source = path.display().to_string();
+                let content = read(path)?;
+                let is_js = path.extension().is_some_and(|extension| {
+                    ["js", "cjs", "mjs"].contains(&extension.to_str().unwrap_or_default())
+                });
+                let config = if is_js {
+                    js_object(&content)
+                } else {
+                    // JSON is read as YAML too
+                    serde_yaml::from_str::<Value>(&content).ok()
+                };
+                (source, config)
+            }
+            None => {
+                let package = read_yaml(Path::new("package.json"))?;
+                match package.and_then(|package| package.get("commitlint").cloned()) {
+                    Some(config) => ("package.json".to_string(), Some(config)),
+                    None => return Ok(()),
+                }
+            }
+        };
+        self.sources.push(source.clone());
+
+        let Some(config) = config else {
+            self.skip(
+                &source,
+                "couldn't be read, only configurations exporting a plain object are supported",
+            );
+            return Ok(());
+        };
+
+        for extended in strings(config.get("extends")).unwrap_or_default() {
+            if extended.ends_with("config-conventional") {
+                self.lint.types = Some(CONVENTIONAL_TYPES.iter().map(|t| t.to_string()).collect());
+                self.lint.max_body_line_length = Some(100);
+            } else {
+                self.skip(&source, format!("extending '{}' isn't supported", extended));
+            }
+        }
+
+        let rules = config.get("rules").and_then(Value::as_mapping);
+        let has_subject_max = rules.is_some_and(|rules| rules.contains_key("subject-max-length"));
+        for (name, rule) in rules.into_iter().flatten() {
+            let name = name.as_str().unwrap_or_default();
+            let at = format!("{}: rule '{}'", source, name);
+            let level = match rule.get(0) {
+                // `RuleConfigSeverity.Disabled` from @commitlint/types
+                Some(Value::String(level)) if level.ends_with("Disabled") => 0,
+                level => level.and_then(Value::as_u64).unwrap_or(2),
+            };
+            let applies = rule.get(1).and_then(Value::as_str).unwrap_or("always");
+            let value = rule.get(2);
+            if level == 0 {
+                continue;
+            }
+            if applies != "always" {
+                self.skip(&at, format!("'{}' isn't supported", applies));
+                continue;
+            }
+
+            let length = value.and_then(Value::as_u64).map(|length| length as usize);
+            match name {
+                "type-enum" => self.lint.types = strings(value),
+                "scope-enum" => self.lint.scopes = strings(value),
+                "subject-min-length" | "header-min-length" => self.lint.min_subject_length = length,
+                "subject-max-length" => self.lint.max_subject_length = length,
+                "header-max-length" if !has_subject_max => {
+                    self.lint.max_subject_length = length;
+                    self.lint.notes.insert(
+                        "max_subject_length",
+                        "partly translated from 'header-max-length', which also counts the type \
+                         and scope"
+                            .to_string(),
+                    );
+                }
+                "body-max-line-length" => self.lint.max_body_line_length = length,
+                _ => self.skip(&at, "has no athira equivalent"),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn read(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path).map_err(|e| HookError::FileError {
+        path: path.to_path_buf(),
+        source: e,
+    })
+}
+
+fn read_yaml(path: &Path) -> Result<Option<Value>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let value = serde_yaml::from_str::<Value>(&read(path)?)
+        .map_err(|e| HookError::ConfigError(format!("{}: {}", path.display(), e)))?;
+    Ok(Some(value))
+}
+
+// A string or a list of strings
+fn strings(value: Option<&Value>) -> Option<Vec<String>> {
+    match value? {
+        Value::String(value) => Some(vec![value.clone()]),
+        Value::Sequence(items) => Some(
+            items
+                .iter()
+                .filter_map(|item| item.as_str().map(str::to_string))
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+// The commands of a shell script, without the shebang, comments and the
+// lines husky adds itself. Continued lines are joined, and shell variables
+// like `${HOME}` are escaped so athira leaves them to the shell.
+fn script_lines(content: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+
+    for line in content.lines() {
+        let line = &line.trim().replace("${", "$${");
+        if current.is_empty()
+            && (line.is_empty() || line.starts_with('#') || line.contains("husky.sh"))
+        {
+            continue;
+        }
+        match line.strip_suffix('\\') {
+            Some(start) => current.push_str(start),
+            None => {
+                current.push_str(line);
+                lines.push(std::mem::take(&mut current));
+            }
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+// Lines that only work together with the rest of the script
+fn is_control_flow(line: &str) -> bool {
+    let keyword = Regex::new(
+        r"^(if|then|else|elif|fi|for|while|until|do|done|case|esac|export|cd|set|exit|return)\b",
+    )
+    .unwrap();
+    // Assignments, function definitions and braces
+    let statement = Regex::new(r"^\w+=|\(\)\s*\{?$|^[{}]").unwrap();
+    keyword.is_match(line) || statement.is_match(line)
+}
+
+// Globs matching the same files as a simple file regex, like `\.py$`,
+// `^docs/` or `^src/.*\.(ts|tsx)$`
+fn regex_globs(regex: &str) -> Option<Vec<String>> {
+    let simple =
+        Regex::new(r"^(\^)?((?:[\w-]+/)*)(?:\.\*)?(?:\\\.(?:(\w+)|\((\w+(?:\|\w+)*)\))\$)?$")
+            .unwrap();
+    let caps = simple.captures(regex)?;
+
+    let anchored = caps.get(1).is_some();
+    let dir = caps.get(2).map_or("", |dir| dir.as_str());
+    let extensions = caps
+        .get(3)
+        .or(caps.get(4))
+        .map(|extensions| extensions.as_str().split('|').collect::<Vec<_>>())
+        .unwrap_or_default();
+    if dir.is_empty() && extensions.is_empty() {
+        return None;
+    }
+
+    // Without `^` the directory may be anywhere in the path
+    let prefix = match (dir.is_empty(), anchored) {
+        (true, _) => String::new(),
+        (false, true) => format!("{}**/", dir),
+        (false, false) => format!("**/{}**/", dir),
+    };
+    if extensions.is_empty() {
+        return Some(vec![prefix.trim_end_matches('/').to_string()]);
+    }
+    Some(
+        extensions
+            .into_iter()
+            .map(|extension| format!("{}*.{}", prefix, extension))
+            .collect(),
+    )
+}
+
+// The object literal a JavaScript configuration exports, when it's plain
+// data that reads as YAML
+fn js_object(content: &str) -> Option<Value> {
+    let start = content
+        .find("module.exports")
+        .or_else(|| content.find("export default"))?;
+    let body = &content[start..];
+    let body = &body[body.find('{')?..=body.rfind('}')?];
+
+    let comments = Regex::new(r"(?m)//.*$|/\*(?s:.*?)\*/").unwrap();
+    let trailing_commas = Regex::new(r",(\s*[}\]])").unwrap();
+    let body = comments.replace_all(body, "");
+    let body = trailing_commas.replace_all(&body, "$1");
+
+    serde_yaml::from_str::<Value>(&body)
+        .ok()
+        .filter(Value::is_mapping)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_configs_only_get_the_migrated_rules() {
+        let migration = Migration {
+            lint: LintRules {
+                types: Some(vec!["feat".into(), "fix".into()]),
+                max_subject_length: Some(72),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut config = Migration::new_config();
+        migration.apply(&mut config);
+
+        assert_eq!(config.lint.types, ["feat", "fix"]);
+        assert!(config.lint.scopes.is_empty());
+        assert_eq!(config.lint.max_subject_length, 72);
+        let defaults = LinterConfig::default();
+        assert_eq!(config.lint.min_subject_length, defaults.min_subject_length);
+        assert!(config.hooks.is_empty() && config.scripts.is_empty());
+    }
+
+    #[test]
+    fn new_configs_without_commitlint_keep_the_default_rules() {
+        let migration = Migration {
+            hooks: vec![(
+                "pre-commit".into(),
+                Hook {
+                    command: "npm test".into(),
+                    ..Default::default()
+                },
+            )],
+            ..Default::default()
+        };
+        let mut config = Migration::new_config();
+        assert_eq!(migration.apply(&mut config), 1);
+
+        assert!(config.lint.types.is_empty() && config.lint.scopes.is_empty());
+        assert_eq!(config.hooks["pre-commit"].len(), 1);
+    }
+}
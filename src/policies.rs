@@ -0,0 +1,316 @@
+use crate::config::PoliciesConfig;
+use crate::error::Result;
+use crate::files::FileFilter;
+use crate::git::GitRepo;
+use std::path::Path;
+
+// Tables of Cargo.toml whose changes have to be reflected in Cargo.lock
+const DEPENDENCY_TABLES: &[&str] = &["dependencies", "dev-dependencies", "build-dependencies"];
+
+/// The `policies:` section, checked against the staged changes on pre-commit
+pub struct StagedPolicy<'a> {
+    config: &'a PoliciesConfig,
+    forbidden: FileFilter,
+    companions: Vec<(FileFilter, FileFilter)>,
+}
+
+impl<'a> StagedPolicy<'a> {
+    pub fn new(config: &'a PoliciesConfig) -> Result<Self> {
+        let forbidden = config
+            .forbidden_paths
+            .iter()
+            .map(|pattern| directory_pattern(pattern))
+            .collect::<Vec<_>>();
+
+        let companions = config
+            .companions
+            .iter()
+            .map(|rule| {
+                Ok((
+                    FileFilter::new(&rule.when, &[])?,
+                    FileFilter::new(&rule.require, &[])?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            config,
+            forbidden: FileFilter::new(&forbidden, &[])?,
+            companions,
+        })
+    }
+
+    /// Checks the staged changes, returning every rule they break
+    pub fn check(&self, repo: &GitRepo) -> Result<Vec<String>> {
+        let mut violations = Vec::new();
+        if self.config.is_empty() {
+            return Ok(violations);
+        }
+
+        // Every changed file, deletions included
+        let changes = repo.staged_numstat()?;
+        let changed = changes
+            .iter()
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+
+        if let Some(max_files) = self.config.max_files {
+            if changed.len() > max_files {
+                violations.push(format!(
+                    "{} files changed, at most {} are allowed per commit",
+                    changed.len(),
+                    max_files
+                ));
+            }
+        }
+
+        if let Some(max_added_lines) = self.config.max_added_lines {
+            // Binary files don't count towards the added lines
+            let added = changes
+                .iter()
+                .filter_map(|(_, added)| *added)
+                .sum::<usize>();
+            if added > max_added_lines {
+                violations.push(format!(
+                    "{} lines added, at most {} are allowed per commit",
+                    added, max_added_lines
+                ));
+            }
+        }
+
+        if self.forbidden.is_active() {
+            for file in self.forbidden.filter(&repo.staged_files()?) {
+                violations.push(format!("'{}' matches a forbidden path", file));
+            }
+        }
+
+        for ((when, require), rule) in self.companions.iter().zip(&self.config.companions) {
+            let triggers = when.filter(&changed);
+            if !triggers.is_empty() && require.filter(&changed).is_empty() {
+                violations.push(format!(
+                    "'{}' changed without a change to {}",
+                    triggers[0],
+                    rule.require
+                        .iter()
+                        .map(|pattern| format!("'{}'", pattern))
+                        .collect::<Vec<_>>()
+                        .join(" or ")
+                ));
+            }
+        }
+
+        if self.config.cargo_lock {
+            violations.extend(check_cargo_lock(repo, &changed)?);
+        }
+
+        Ok(violations)
+    }
+}
+
+// `target/` forbids everything under any `target` directory, like in
+// `.gitignore`
+fn directory_pattern(pattern: &str) -> String {
+    match pattern.strip_suffix('/') {
+        Some(dir) if dir.contains('/') => format!("{}/**", dir),
+        Some(dir) => format!("**/{}/**", dir),
+        None => pattern.to_string(),
+    }
+}
+
+fn check_cargo_lock(repo: &GitRepo, changed: &[String]) -> Result<Vec<String>> {
+    let mut violations = Vec::new();
+
+    for manifest in changed.iter().filter(|file| is_named(file, "Cargo.toml")) {
+        let before = repo.show_file(&format!("HEAD:{}", manifest))?;
+        let Some(after) = repo.show_file(&format!(":{}", manifest))? else {
+            continue;
+        };
+        if dependencies(before.as_deref()) == dependencies(Some(&after)) {
+            continue;
+        }
+
+        // The lock file may belong to a workspace further up
+        let lock_changed = changed.iter().any(|file| {
+            is_named(file, "Cargo.lock")
+                && Path::new(manifest)
+                    .parent()
+                    .is_some_and(|dir| dir.starts_with(Path::new(file).parent().unwrap()))
+        });
+        if !lock_changed {
+            violations.push(format!(
+                "'{}' changed its dependencies but Cargo.lock wasn't updated",
+                manifest
+            ));
+        }
+    }
+
+    Ok(violations)
+}
+
+fn is_named(file: &str, name: &str) -> bool {
+    Path::new(file).file_name().is_some_and(|file| file == name)
+}
+
+// The dependency tables of a manifest, including target-specific and
+// workspace dependencies. Manifests that don't parse compare as empty.
+fn dependencies(manifest: Option<&str>) -> toml::Table {
+    let Some(manifest) = manifest.and_then(|content| content.parse::<toml::Table>().ok()) else {
+        return toml::Table::new();
+    };
+
+    let mut tables = toml::Table::new();
+    let mut collect = |prefix: &str, table: &toml::Table| {
+        for name in DEPENDENCY_TABLES {
+            if let Some(deps) = table.get(*name) {
+                tables.insert(format!("{}{}", prefix, name), deps.clone());
+            }
+        }
+    };
+
+    collect("", &manifest);
+    if let Some(targets) = manifest.get("target").and_then(toml::Value::as_table) {
+        for (target, table) in targets {
+            if let Some(table) = table.as_table() {
+                collect(&format!("target.{}.", target), table);
+            }
+        }
+    }
+    if let Some(deps) = manifest
+        .get("workspace")
+        .and_then(|workspace| workspace.get("dependencies"))
+    {
+        tables.insert("workspace.dependencies".to_string(), deps.clone());
+    }
+
+    tables
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::tests::{git, in_dir, scratch_repo};
+    use std::path::PathBuf;
+
+    // The rules the staged changes in `dir` break under `policies`
+    fn violations(dir: &Path, policies: &str) -> Vec<String> {
+        let config: PoliciesConfig = serde_yaml::from_str(policies).unwrap();
+        let repo = GitRepo {
+            hooks_dir: PathBuf::new(),
+        };
+        in_dir(dir, || {
+            StagedPolicy::new(&config).unwrap().check(&repo).unwrap()
+        })
+    }
+
+    fn stage(dir: &Path, files: &[(&str, &str)]) {
+        for (file, content) in files {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+            git(dir, &["add", file]);
+        }
+    }
+
+    #[test]
+    fn directories_match_at_any_depth() {
+        assert_eq!(directory_pattern("target/"), "**/target/**");
+        assert_eq!(directory_pattern("web/dist/"), "web/dist/**");
+        assert_eq!(directory_pattern("*.env"), "*.env");
+    }
+
+    #[test]
+    fn compares_only_dependencies() {
+        let before = "[package]\nversion = \"1.0.0\"\n[dependencies]\nregex = \"1\"\n";
+        let bumped = "[package]\nversion = \"1.1.0\"\n[dependencies]\nregex = \"1\"\n";
+        let added =
+            "[dependencies]\nregex = \"1\"\n[target.'cfg(unix)'.dependencies]\nlibc = \"0.2\"\n";
+        assert_eq!(dependencies(Some(before)), dependencies(Some(bumped)));
+        assert_ne!(dependencies(Some(before)), dependencies(Some(added)));
+        assert!(dependencies(Some("not toml [")).is_empty());
+        assert!(dependencies(None).is_empty());
+    }
+
+    #[test]
+    fn limits_the_size_of_commits() {
+        let dir = scratch_repo("policies-size");
+        stage(
+            &dir,
+            &[
+                ("a.txt", "1\n2\n3\n"),
+                ("b.txt", "1\n"),
+                ("c.bin", "\0\0\0"),
+            ],
+        );
+
+        assert!(violations(&dir, "{max_files: 3, max_added_lines: 4}").is_empty());
+        assert_eq!(
+            violations(&dir, "{max_files: 2, max_added_lines: 3}"),
+            [
+                "3 files changed, at most 2 are allowed per commit",
+                "4 lines added, at most 3 are allowed per commit",
+            ]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_forbidden_paths() {
+        let dir = scratch_repo("policies-forbidden");
+        stage(
+            &dir,
+            &[
+                ("src/main.rs", ""),
+                ("web/target/out.js", ""),
+                ("café.env", ""),
+            ],
+        );
+
+        let found = violations(&dir, "{forbidden_paths: ['target/', '*.env']}");
+        assert_eq!(
+            found,
+            [
+                "'café.env' matches a forbidden path",
+                "'web/target/out.js' matches a forbidden path",
+            ]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn requires_companions() {
+        let dir = scratch_repo("policies-companions");
+        stage(&dir, &[("schema/user.sql", "")]);
+
+        let rule = "{companions: [{when: 'schema/*.sql', require: ['migrations/**', 'NOTES.md']}]}";
+        assert_eq!(
+            violations(&dir, rule),
+            ["'schema/user.sql' changed without a change to 'migrations/**' or 'NOTES.md'"]
+        );
+        stage(&dir, &[("migrations/002.sql", "")]);
+        assert!(violations(&dir, rule).is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn requires_cargo_lock_with_new_dependencies() {
+        let dir = scratch_repo("policies-cargo");
+        let manifest = "[package]\nname = \"a\"\nversion = \"1.0.0\"\n";
+        stage(&dir, &[("Cargo.toml", manifest), ("Cargo.lock", "")]);
+        git(&dir, &["commit", "-q", "-m", "init"]);
+
+        // A new version alone doesn't change the lock file's dependencies
+        stage(&dir, &[("Cargo.toml", &manifest.replace("1.0.0", "1.1.0"))]);
+        assert!(violations(&dir, "{cargo_lock: true}").is_empty());
+
+        let with_dependency = format!("{}[dependencies]\nregex = \"1\"\n", manifest);
+        stage(&dir, &[("Cargo.toml", &with_dependency)]);
+        assert_eq!(
+            violations(&dir, "{cargo_lock: true}"),
+            ["'Cargo.toml' changed its dependencies but Cargo.lock wasn't updated"]
+        );
+
+        stage(&dir, &[("Cargo.lock", "# regex\n")]);
+        assert!(violations(&dir, "{cargo_lock: true}").is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,66 @@
+/// Configurations built into athira, used with `extends: athira:<name>`
+const PRESETS: &[(&str, &str)] = &[
+    ("conventional", CONVENTIONAL),
+    ("rust", RUST),
+    ("node", NODE),
+];
+
+/// The prefix naming a preset in `extends`
+pub const PREFIX: &str = "athira:";
+
+// Conventional commit messages, checked on commit-msg
+const CONVENTIONAL: &str = r#"
+hooks:
+  commit-msg:
+    - id: conventional-commit
+      command: ${athira}
+      args: [commit, validate, $1]
+lint:
+  types: [feat, fix, docs, style, refactor, perf, test, build, ci, chore, revert]
+  min_subject_length: 3
+  max_subject_length: 72
+  max_body_line_length: 100
+"#;
+
+const RUST: &str = r#"
+hooks:
+  pre-commit:
+    - id: cargo-fmt
+      command: cargo fmt --all -- --check
+      glob: "*.rs"
+    - id: cargo-clippy
+      command: cargo clippy --all-targets -- -D warnings
+      glob: "*.rs"
+  pre-push:
+    - id: cargo-test
+      command: cargo test
+"#;
+
+const NODE: &str = r#"
+hooks:
+  pre-commit:
+    - id: prettier
+      command: npx prettier --check ${files}
+      glob: ["*.js", "*.jsx", "*.ts", "*.tsx", "*.json", "*.css", "*.md"]
+    - id: eslint
+      command: npx eslint ${files}
+      glob: ["*.js", "*.jsx", "*.ts", "*.tsx"]
+  pre-push:
+    - id: npm-test
+      command: npm test
+"#;
+
+/// The configuration of a preset, by its name without the prefix
+pub fn find(name: &str) -> Option<&'static str> {
+    PRESETS
+        .iter()
+        .find(|(preset, _)| *preset == name)
+        .map(|(_, content)| *content)
+}
+
+pub fn names() -> Vec<String> {
+    PRESETS
+        .iter()
+        .map(|(name, _)| format!("{}{}", PREFIX, name))
+        .collect()
+}
@@ -0,0 +1,322 @@
+use crate::config::{Hook, Language, RepoConfig};
+use crate::error::{HookError, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// File at the root of a hook repository listing the hooks it provides
+pub const MANIFEST: &str = "athira-hooks.yaml";
+
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    hooks: Vec<Hook>,
+}
+
+/// A hook definition from a checked out repository
+#[derive(Debug)]
+pub struct HookDefinition {
+    pub repo: String,
+    /// Where the repository is checked out, exposed as `${hook_repo}`
+    pub dir: PathBuf,
+    pub hook: Hook,
+}
+
+/// The hook definitions of every configured repository
+pub struct RepoSet {
+    definitions: Vec<HookDefinition>,
+}
+
+impl RepoSet {
+    /// Checks out every repository (cloning into the cache when needed) and
+    /// reads its hook definitions
+    pub fn load(repos: &[RepoConfig]) -> Result<Self> {
+        let mut definitions = Vec::new();
+
+        for repo in repos {
+            let dir = checkout(repo)?;
+            let manifest_path = dir.join(MANIFEST);
+            let content =
+                std::fs::read_to_string(&manifest_path).map_err(|e| HookError::RepoError {
+                    repo: repo.repo.clone(),
+                    reason: format!("Failed to read {}: {}", MANIFEST, e),
+                })?;
+            let manifest: Manifest =
+                serde_yaml::from_str(&content).map_err(|e| HookError::RepoError {
+                    repo: repo.repo.clone(),
+                    reason: format!("Invalid {}: {}", MANIFEST, e),
+                })?;
+
+            for hook in manifest.hooks {
+                if hook.id.is_none() || hook.command.is_empty() {
+                    return Err(HookError::RepoError {
+                        repo: repo.repo.clone(),
+                        reason: format!("Every hook in {} needs an 'id' and a 'command'", MANIFEST),
+                    });
+                }
+                definitions.push(HookDefinition {
+                    repo: repo.repo.clone(),
+                    dir: dir.clone(),
+                    hook,
+                });
+            }
+        }
+
+        Ok(Self { definitions })
+    }
+
+    pub fn find(&self, id: &str) -> Result<&HookDefinition> {
+        let mut matches = self
+            .definitions
+            .iter()
+            .filter(|definition| definition.hook.id.as_deref() == Some(id));
+
+        let definition = matches.next().ok_or_else(|| {
+            HookError::ConfigError(format!("No configured repository defines hook '{}'", id))
+        })?;
+        if let Some(other) = matches.next() {
+            return Err(HookError::ConfigError(format!(
+                "Hook '{}' is defined by both {} and {}",
+                id, definition.repo, other.repo
+            )));
+        }
+
+        Ok(definition)
+    }
+
+    /// Turns a hook that `uses` a definition into a runnable hook. Settings
+    /// on the local entry take precedence over the definition's.
+    pub fn resolve(&self, local: &Hook) -> Result<Hook> {
+        let Some(uses) = &local.uses else {
+            return Ok(local.clone());
+        };
+
+        let definition = self.find(uses)?;
+        let def = &definition.hook;
+        let dir = definition.dir.display().to_string();
+
+        let args = if local.args.is_empty() {
+            def.args
+                .iter()
+                .map(|arg| arg.replace("${hook_repo}", &dir))
+                .collect()
+        } else {
+            local.args.clone()
+        };
+
+        Ok(Hook {
+            id: local.id.clone().or_else(|| def.id.clone()),
+            uses: None,
+            command: def
+                .command
+                .replace("${hook_repo}", &shell_words::quote(&dir)),
+            args,
+            glob: if local.glob.is_empty() {
+                def.glob.clone()
+            } else {
+                local.glob.clone()
+            },
+            exclude: if local.exclude.is_empty() {
+                def.exclude.clone()
+            } else {
+                local.exclude.clone()
+            },
+            working_dir: local
+                .working_dir
+                .clone()
+                .or_else(|| def.working_dir.clone()),
+            timeout: local.timeout.or(def.timeout),
+            allow_failure: local.allow_failure || def.allow_failure,
+            continue_on_error: local.continue_on_error || def.continue_on_error,
+            retries: if local.retries > 0 {
+                local.retries
+            } else {
+                def.retries
+            },
+            shell: local.shell.or(def.shell),
+            language: if local.language != Language::System {
+                local.language
+            } else {
+                def.language
+            },
+            additional_dependencies: if local.additional_dependencies.is_empty() {
+                def.additional_dependencies.clone()
+            } else {
+                local.additional_dependencies.clone()
+            },
+            when: local.when.clone().or_else(|| def.when.clone()),
+        })
+    }
+}
+
+/// Directory holding cached checkouts of hook repositories
+pub fn cache_dir() -> Result<PathBuf> {
+    dirs::cache_dir()
+        .map(|dir| dir.join("athira").join("repos"))
+        .ok_or_else(|| HookError::ConfigError("Could not determine the cache directory".into()))
+}
+
+// Relative local paths are resolved against the current directory so the
+// same repository always maps to the same cache entry
+fn source_url(repo: &str) -> String {
+    let path = Path::new(repo);
+    if repo.contains("://") || repo.contains('@') || !path.exists() {
+        return repo.to_string();
+    }
+
+    std::env::current_dir()
+        .ok()
+        .and_then(|dir| dir.join(path).canonicalize().ok())
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|| repo.to_string())
+}
+
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Returns the checkout of `repo` at its pinned revision, cloning it into
+/// the cache the first time it is needed
+pub fn checkout(repo: &RepoConfig) -> Result<PathBuf> {
+    let url = source_url(&repo.repo);
+    let target = cache_dir()?.join(format!("{}@{}", sanitize(&url), sanitize(&repo.rev)));
+    if target.exists() {
+        return Ok(target);
+    }
+
+    let parent = target.parent().unwrap_or(&target).to_path_buf();
+    std::fs::create_dir_all(&parent).map_err(|e| HookError::FileError {
+        path: parent.clone(),
+        source: e,
+    })?;
+
+    // Clone next to the target and move it into place once checked out, so
+    // an interrupted clone never looks like a valid cache entry
+    let staging = parent.join(format!(".tmp-{}", std::process::id()));
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging)?;
+    }
+
+    let result = git(
+        &repo.repo,
+        None,
+        &["clone", "--quiet", &url, &staging.display().to_string()],
+    )
+    .and_then(|_| {
+        git(
+            &repo.repo,
+            Some(&staging),
+            &["checkout", "--quiet", &repo.rev],
+        )
+    });
+    if let Err(e) = result {
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(e);
+    }
+
+    std::fs::rename(&staging, &target).map_err(|e| HookError::FileError {
+        path: target.clone(),
+        source: e,
+    })?;
+    Ok(target)
+}
+
+/// The newest tag of the repository, or the commit of its default branch
+/// when it has no tags
+pub fn latest_rev(repo: &str) -> Result<String> {
+    let url = source_url(repo);
+
+    let tags = git(repo, None, &["ls-remote", "--tags", "--refs", &url])?;
+    let newest = tags
+        .lines()
+        .filter_map(|line| line.split('\t').nth(1))
+        .filter_map(|name| name.strip_prefix("refs/tags/"))
+        .max_by_key(|tag| version_key(tag));
+    if let Some(tag) = newest {
+        return Ok(tag.to_string());
+    }
+
+    let head = git(repo, None, &["ls-remote", &url, "HEAD"])?;
+    head.split_whitespace()
+        .next()
+        .map(str::to_string)
+        .ok_or_else(|| HookError::RepoError {
+            repo: repo.to_string(),
+            reason: "Repository has no tags and no HEAD".into(),
+        })
+}
+
+// Orders tags like v1.10.0 after v1.9.2; tags without numbers sort first
+fn version_key(tag: &str) -> (Vec<u64>, String) {
+    let numbers = tag
+        .trim_start_matches(|c: char| !c.is_ascii_digit())
+        .split(|c: char| !c.is_ascii_digit())
+        .map_while(|part| part.parse().ok())
+        .collect();
+    (numbers, tag.to_string())
+}
+
+fn git(repo: &str, dir: Option<&Path>, args: &[&str]) -> Result<String> {
+    let output = git_command(dir).args(args).output()?;
+    if !output.status.success() {
+        return Err(HookError::RepoError {
+            repo: repo.to_string(),
+            reason: format!(
+                "'git {}' failed: {}",
+                args.first().copied().unwrap_or_default(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        });
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// Hooks run with git's variables pointing at the user's repository, which
+// would make the commands act on it instead of the cache
+fn git_command(dir: Option<&Path>) -> Command {
+    let mut command = Command::new("git");
+    command
+        .env_remove("GIT_DIR")
+        .env_remove("GIT_INDEX_FILE")
+        .env_remove("GIT_WORK_TREE");
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn git_ignores_the_hook_environment() {
+        let command = git_command(None);
+        let removed: Vec<_> = command
+            .get_envs()
+            .filter(|(_, value)| value.is_none())
+            .map(|(name, _)| name.to_str().unwrap())
+            .collect();
+        assert_eq!(removed, ["GIT_DIR", "GIT_INDEX_FILE", "GIT_WORK_TREE"]);
+    }
+
+    #[test]
+    fn orders_versions_numerically() {
+        let mut tags = vec!["v1.10.0", "v1.9.2", "latest", "v2.0.0-rc1", "v1.9.10"];
+        tags.sort_by_key(|tag| version_key(tag));
+        assert_eq!(
+            tags,
+            ["latest", "v1.9.2", "v1.9.10", "v1.10.0", "v2.0.0-rc1"]
+        );
+    }
+}
@@ -0,0 +1,510 @@
+use crate::builtins::{self, Builtin, Source};
+use crate::config::Hook;
+use crate::error::{HookError, Result};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Status output goes to stderr: git reads the stdout of some hooks
+// (e.g. fsmonitor-watchman), so only the commands themselves write there
+
+// How often a running command is polled while waiting for a timeout
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How a hook command is started
+pub enum Invocation {
+    /// A command line run by `sh -c`, with the hook arguments as $1, $2, ...
+    Shell(String),
+    /// A program and its arguments, executed without a shell
+    Exec(Vec<String>),
+    /// A check run inside athira over the given files
+    Builtin {
+        check: Builtin,
+        args: Vec<String>,
+        files: Vec<String>,
+        source: Source,
+    },
+}
+
+impl fmt::Display for Invocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Shell(command_line) => write!(f, "{}", command_line),
+            Self::Exec(argv) => write!(f, "{}", shell_words::join(argv)),
+            Self::Builtin {
+                check, args, files, ..
+            } => {
+                write!(f, "{}{}", builtins::PREFIX, check.name())?;
+                if !args.is_empty() {
+                    write!(f, " {}", shell_words::join(args))?;
+                }
+                write!(f, " ({} files)", files.len())
+            }
+        }
+    }
+}
+
+/// A hook command with its variables resolved, ready to execute
+pub struct PreparedCommand<'a> {
+    pub hook: &'a Hook,
+    pub label: String,
+    pub invocation: Invocation,
+    /// Extra environment variables, e.g. the PATH of a managed toolchain
+    pub env: Vec<(String, String)>,
+    /// Set when the command is skipped before anything runs
+    pub skip: Option<SkipReason>,
+}
+
+/// Why a command is skipped without running
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    /// Through `SKIP`
+    Requested,
+    /// None of the files match its `glob`/`exclude` patterns
+    NoMatchingFiles,
+    /// Its `when` condition doesn't hold, e.g. `os: windows`
+    Condition(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandStatus {
+    Passed,
+    Failed,
+    Warned,
+    Skipped,
+}
+
+impl CommandStatus {
+    fn colored(self) -> ColoredString {
+        match self {
+            Self::Passed => "passed".green(),
+            Self::Failed => "failed".red(),
+            Self::Warned => "warned".yellow(),
+            Self::Skipped => "skipped".dimmed(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommandOutcome {
+    pub label: String,
+    pub status: CommandStatus,
+    pub duration_ms: u64,
+    pub attempts: u32,
+    pub timed_out: bool,
+}
+
+/// The result of one hook invocation, kept in `.git/thira/last-run.json`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub hook: String,
+    pub args: Vec<String>,
+    /// Seconds since the Unix epoch
+    pub started_at: u64,
+    pub duration_ms: u64,
+    pub commands: Vec<CommandOutcome>,
+}
+
+impl RunRecord {
+    const FILE_NAME: &'static str = "last-run.json";
+
+    pub fn path(git_dir: &Path) -> PathBuf {
+        git_dir.join("thira").join(Self::FILE_NAME)
+    }
+
+    pub fn save(&self, git_dir: &Path) -> Result<()> {
+        let path = Self::path(git_dir);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| HookError::FileError {
+                path: parent.to_path_buf(),
+                source: e,
+            })?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| HookError::ConfigError(format!("Failed to encode run record: {}", e)))?;
+        std::fs::write(&path, content).map_err(|e| HookError::FileError { path, source: e })
+    }
+
+    pub fn load(git_dir: &Path) -> Result<Option<Self>> {
+        let path = Self::path(git_dir);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = std::fs::read_to_string(&path).map_err(|e| HookError::FileError {
+            path: path.clone(),
+            source: e,
+        })?;
+        serde_json::from_str(&content).map(Some).map_err(|e| {
+            HookError::ConfigError(format!("Invalid run record {}: {}", path.display(), e))
+        })
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.commands
+            .iter()
+            .all(|c| c.status != CommandStatus::Failed)
+    }
+
+    pub fn ensure_success(&self) -> Result<()> {
+        let failed = self
+            .commands
+            .iter()
+            .filter(|c| c.status == CommandStatus::Failed)
+            .count();
+        if failed > 0 {
+            return Err(HookError::HookExecutionError {
+                hook_name: self.hook.clone(),
+                reason: format!("{} command(s) failed", failed),
+            });
+        }
+        Ok(())
+    }
+
+    /// Prints a table of every command with its status and duration
+    pub fn print_summary(&self) {
+        let width = self
+            .commands
+            .iter()
+            .map(|c| c.label.chars().count())
+            .max()
+            .unwrap_or(0)
+            .clamp("COMMAND".len(), 48);
+
+        eprintln!();
+        eprintln!(
+            "  {}  {}  {}",
+            format!("{:<width$}", "COMMAND").bold(),
+            format!("{:<7}", "STATUS").bold(),
+            format!("{:>9}", "DURATION").bold()
+        );
+        for command in &self.commands {
+            let mut notes = Vec::new();
+            if command.timed_out {
+                notes.push("timed out".to_string());
+            }
+            if command.attempts > 1 {
+                notes.push(format!("{} attempts", command.attempts));
+            }
+
+            eprintln!(
+                "  {:<width$}  {:<7}  {:>9}  {}",
+                truncate(&command.label, width),
+                // Pad before colouring so escape codes don't skew alignment
+                format!("{:<7}", command.status.colored()),
+                format_duration(command.duration_ms),
+                notes.join(", ").dimmed()
+            );
+        }
+
+        let count = |status| self.commands.iter().filter(|c| c.status == status).count();
+        let summary = format!(
+            "{}: {} passed, {} warned, {} failed, {} skipped in {}",
+            self.hook,
+            count(CommandStatus::Passed),
+            count(CommandStatus::Warned),
+            count(CommandStatus::Failed),
+            count(CommandStatus::Skipped),
+            format_duration(self.duration_ms)
+        );
+        eprintln!();
+        if !self.succeeded() {
+            eprintln!("{}", summary.red().bold());
+        } else if count(CommandStatus::Warned) > 0 {
+            eprintln!("{}", summary.yellow().bold());
+        } else {
+            eprintln!("{}", summary.green().bold());
+        }
+    }
+}
+
+fn truncate(label: &str, width: usize) -> String {
+    if label.chars().count() <= width {
+        return label.to_string();
+    }
+    let mut truncated = label.chars().take(width - 1).collect::<String>();
+    truncated.push('…');
+    truncated
+}
+
+fn format_duration(ms: u64) -> String {
+    if ms < 1000 {
+        format!("{}ms", ms)
+    } else {
+        format!("{:.2}s", ms as f64 / 1000.0)
+    }
+}
+
+pub struct HookRunner<'a> {
+    hook_name: &'a str,
+    args: &'a [String],
+    stdin: Option<&'a [u8]>,
+}
+
+impl<'a> HookRunner<'a> {
+    pub fn new(hook_name: &'a str, args: &'a [String], stdin: Option<&'a [u8]>) -> Self {
+        Self {
+            hook_name,
+            args,
+            stdin,
+        }
+    }
+
+    /// Runs every command in order, honouring each hook's failure policy.
+    /// Command failures are reported in the returned record, not as errors.
+    pub fn run(&self, commands: &[PreparedCommand]) -> Result<RunRecord> {
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let start = Instant::now();
+        let mut outcomes = Vec::with_capacity(commands.len());
+        let mut stopped = false;
+
+        for prepared in commands {
+            if stopped || prepared.skip.is_some() {
+                outcomes.push(CommandOutcome {
+                    label: prepared.label.clone(),
+                    status: CommandStatus::Skipped,
+                    duration_ms: 0,
+                    attempts: 0,
+                    timed_out: false,
+                });
+                continue;
+            }
+
+            let outcome = self.run_command(prepared)?;
+            if outcome.status == CommandStatus::Failed && !prepared.hook.continue_on_error {
+                stopped = true;
+            }
+            outcomes.push(outcome);
+        }
+
+        Ok(RunRecord {
+            hook: self.hook_name.to_string(),
+            args: self.args.to_vec(),
+            started_at,
+            duration_ms: start.elapsed().as_millis() as u64,
+            commands: outcomes,
+        })
+    }
+
+    fn run_command(&self, prepared: &PreparedCommand) -> Result<CommandOutcome> {
+        let hook = prepared.hook;
+        // Builtins are deterministic, so retrying or timing them out is pointless
+        let max_attempts = match prepared.invocation {
+            Invocation::Builtin { .. } => 1,
+            _ => hook.retries + 1,
+        };
+        let start = Instant::now();
+        let mut attempts = 0;
+        let mut timed_out = false;
+        let mut success = false;
+
+        eprintln!("{} {}", "▶".cyan(), prepared.label);
+
+        // Builtins run in-process, once
+        if let Invocation::Builtin {
+            check,
+            args,
+            files,
+            source,
+        } = &prepared.invocation
+        {
+            attempts = 1;
+            success = check.run(args, files, *source)?;
+            if !success {
+                eprintln!("{}", format!("  ✗ {} failed", check.name()).red());
+            }
+        }
+
+        while attempts < max_attempts {
+            attempts += 1;
+            if attempts > 1 {
+                eprintln!(
+                    "{}",
+                    format!("  ↻ Retrying (attempt {}/{})", attempts, max_attempts).yellow()
+                );
+            }
+
+            let child = self.spawn(prepared)?;
+            match self.wait(child, hook.timeout)? {
+                Some(status) if status.success() => {
+                    success = true;
+                    timed_out = false;
+                    break;
+                }
+                Some(status) => {
+                    timed_out = false;
+                    eprintln!("{}", format!("  ✗ Exited with {}", status).red());
+                }
+                None => {
+                    timed_out = true;
+                    eprintln!(
+                        "{}",
+                        format!("  ✗ Timed out after {}s", hook.timeout.unwrap_or_default()).red()
+                    );
+                }
+            }
+        }
+
+        let status = if success {
+            CommandStatus::Passed
+        } else if hook.allow_failure {
+            CommandStatus::Warned
+        } else {
+            CommandStatus::Failed
+        };
+
+        Ok(CommandOutcome {
+            label: prepared.label.clone(),
+            status,
+            duration_ms: start.elapsed().as_millis() as u64,
+            attempts,
+            timed_out,
+        })
+    }
+
+    fn spawn(&self, prepared: &PreparedCommand) -> Result<Child> {
+        let mut command = match &prepared.invocation {
+            Invocation::Shell(command_line) => {
+                // The hook name becomes $0 so that $1, $2, ... map to the hook arguments
+                let mut command = Command::new("sh");
+                command
+                    .arg("-c")
+                    .arg(command_line)
+                    .arg(self.hook_name)
+                    .args(self.args);
+                command
+            }
+            Invocation::Exec(argv) => {
+                let (program, args) =
+                    argv.split_first()
+                        .ok_or_else(|| HookError::HookExecutionError {
+                            hook_name: self.hook_name.to_string(),
+                            reason: format!("'{}' resolves to an empty command", prepared.label),
+                        })?;
+                let mut command = Command::new(program);
+                command.args(args);
+                command
+            }
+            Invocation::Builtin { .. } => {
+                unreachable!("builtins run in-process")
+            }
+        };
+
+        if let Some(dir) = &prepared.hook.working_dir {
+            command.current_dir(dir);
+        }
+
+        command.envs(prepared.env.iter().map(|(key, value)| (key, value)));
+
+        if self.stdin.is_some() {
+            command.stdin(Stdio::piped());
+        }
+
+        // A command that can time out gets a process group of its own, so
+        // whatever it starts is killed along with it. Others stay in the
+        // terminal's group, where they can read input and get Ctrl-C; for
+        // these, `wait` passes Ctrl-C on.
+        #[cfg(unix)]
+        if prepared.hook.timeout.is_some() {
+            use std::os::unix::process::CommandExt;
+            command.process_group(0);
+            interrupt::watch();
+        }
+
+        let mut child = command.spawn().map_err(|e| HookError::HookExecutionError {
+            hook_name: self.hook_name.to_string(),
+            reason: format!("Failed to start '{}': {}", prepared.label, e),
+        })?;
+
+        // Feed stdin from a separate thread so a command that doesn't read it
+        // can't block us; write errors just mean it exited early
+        if let (Some(data), Some(mut pipe)) = (self.stdin, child.stdin.take()) {
+            let data = data.to_vec();
+            std::thread::spawn(move || {
+                let _ = pipe.write_all(&data);
+            });
+        }
+
+        Ok(child)
+    }
+
+    // Returns None when the command was killed for exceeding its timeout
+    fn wait(&self, mut child: Child, timeout: Option<u64>) -> Result<Option<ExitStatus>> {
+        let Some(limit) = timeout.map(Duration::from_secs) else {
+            return Ok(Some(child.wait()?));
+        };
+
+        let start = Instant::now();
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return Ok(Some(status));
+            }
+            if start.elapsed() >= limit {
+                kill(&mut child)?;
+                child.wait()?;
+                return Ok(None);
+            }
+            #[cfg(unix)]
+            if interrupt::received() {
+                kill(&mut child)?;
+                child.wait()?;
+                return Err(HookError::HookExecutionError {
+                    hook_name: self.hook_name.to_string(),
+                    reason: "Interrupted".to_string(),
+                });
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+// Kills a command started with a timeout and everything it started
+#[cfg(unix)]
+fn kill(child: &mut Child) -> std::io::Result<()> {
+    // SAFETY: kill only sends a signal, to the group spawn created
+    if unsafe { libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL) } == 0 {
+        return Ok(());
+    }
+    child.kill()
+}
+
+#[cfg(not(unix))]
+fn kill(child: &mut Child) -> std::io::Result<()> {
+    child.kill()
+}
+
+// Ctrl-C while a command runs in its own process group, which the terminal
+// doesn't signal
+#[cfg(unix)]
+mod interrupt {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Once;
+
+    static RECEIVED: AtomicBool = AtomicBool::new(false);
+    static WATCH: Once = Once::new();
+
+    extern "C" fn on_interrupt(_: libc::c_int) {
+        RECEIVED.store(true, Ordering::SeqCst);
+    }
+
+    pub fn watch() {
+        WATCH.call_once(|| {
+            let handler = on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            // SAFETY: the handler only stores to an atomic
+            unsafe { libc::signal(libc::SIGINT, handler) };
+        });
+    }
+
+    pub fn received() -> bool {
+        RECEIVED.load(Ordering::SeqCst)
+    }
+}
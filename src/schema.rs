@@ -0,0 +1,186 @@
+use serde_json::{json, Value};
+
+/// JSON Schema of the configuration file, for editors and `thira config
+/// validate`. Keep it in sync with the structs in config.rs.
+pub fn schema() -> Value {
+    let strings = json!({ "type": "array", "items": { "type": "string" } });
+
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "athira configuration",
+        "type": "object",
+        "additionalProperties": false,
+        "properties": {
+            "extends": {
+                "description": "Files and presets like athira:rust to build on",
+                "$ref": "#/definitions/patterns"
+            },
+            "repos": {
+                "description": "Shared hook repositories whose definitions hooks can use",
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "additionalProperties": false,
+                    "required": ["repo", "rev"],
+                    "properties": {
+                        "repo": { "type": "string", "description": "Git URL or local path" },
+                        "rev": { "type": "string", "description": "Tag, branch or commit to check out" }
+                    }
+                }
+            },
+            "hooks": {
+                "description": "Commands to run for each git hook",
+                "type": "object",
+                "additionalProperties": {
+                    "type": "array",
+                    "items": { "$ref": "#/definitions/hook" }
+                }
+            },
+            "scripts": {
+                "description": "Named scripts, run with 'thira scripts run'",
+                "type": "object",
+                "additionalProperties": { "$ref": "#/definitions/script" }
+            },
+            "options": {
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "auto_install": { "type": "boolean" },
+                    "hooks_dir": { "type": "string" },
+                    "migrate_existing": { "enum": ["backup", "chain"] }
+                }
+            },
+            "lint": {
+                "description": "Commit message rules",
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "types": strings,
+                    "scopes": strings,
+                    "min_subject_length": { "type": "integer", "minimum": 1 },
+                    "max_subject_length": { "type": "integer", "minimum": 1 },
+                    "max_body_line_length": { "type": "integer", "minimum": 1 }
+                }
+            },
+            "secrets": {
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "rules": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "additionalProperties": false,
+                            "required": ["id", "regex"],
+                            "properties": {
+                                "id": { "type": "string" },
+                                "regex": { "type": "string" }
+                            }
+                        }
+                    },
+                    "allowlist": { "type": "string" },
+                    "entropy_threshold": { "type": "number", "minimum": 0 }
+                }
+            },
+            "branches": {
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "protected": strings,
+                    "naming": { "type": "string" },
+                    "allow_force_push": { "type": "boolean" }
+                }
+            },
+            "policies": {
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "max_files": { "type": "integer", "minimum": 1 },
+                    "max_added_lines": { "type": "integer", "minimum": 1 },
+                    "forbidden_paths": strings,
+                    "companions": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "additionalProperties": false,
+                            "required": ["when", "require"],
+                            "properties": {
+                                "when": { "$ref": "#/definitions/patterns" },
+                                "require": { "$ref": "#/definitions/patterns" }
+                            }
+                        }
+                    },
+                    "cargo_lock": { "type": "boolean" }
+                }
+            }
+        },
+        "definitions": {
+            "patterns": {
+                "type": ["string", "array"],
+                "items": { "type": "string" }
+            },
+            "condition": {
+                "description": "Only run when every setting matches; entries starting with ! must not",
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "os": { "$ref": "#/definitions/patterns" },
+                    "branch": { "$ref": "#/definitions/patterns" },
+                    "env": { "$ref": "#/definitions/patterns" },
+                    "exists": { "$ref": "#/definitions/patterns" }
+                }
+            },
+            "hook": {
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "id": { "type": "string" },
+                    "uses": { "type": "string" },
+                    "command": { "type": "string" },
+                    "args": strings,
+                    "glob": { "$ref": "#/definitions/patterns" },
+                    "exclude": { "$ref": "#/definitions/patterns" },
+                    "working_dir": { "type": "string" },
+                    "timeout": { "type": "integer", "minimum": 1 },
+                    "allow_failure": { "type": "boolean" },
+                    "continue_on_error": { "type": "boolean" },
+                    "retries": { "type": "integer", "minimum": 0 },
+                    "shell": { "type": "boolean" },
+                    "language": { "enum": ["system", "node", "python", "rust"] },
+                    "additional_dependencies": strings,
+                    "when": { "$ref": "#/definitions/condition" },
+                    "if": { "$ref": "#/definitions/condition" }
+                }
+            },
+            "script": {
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "parallel": { "type": "boolean" },
+                    "max_threads": { "type": "integer", "minimum": 1 },
+                    "commands": {
+                        "type": "array",
+                        "items": { "$ref": "#/definitions/command" }
+                    }
+                }
+            },
+            "command": {
+                "type": "object",
+                "additionalProperties": false,
+                "required": ["command"],
+                "properties": {
+                    "command": { "type": "string" },
+                    "description": { "type": "string" },
+                    "working_dir": { "type": "string" },
+                    "env": {
+                        "type": "object",
+                        "additionalProperties": { "type": "string" }
+                    },
+                    "env_file": { "type": "string", "description": ".env file of variables to set" },
+                    "when": { "$ref": "#/definitions/condition" },
+                    "if": { "$ref": "#/definitions/condition" }
+                }
+            }
+        }
+    })
+}
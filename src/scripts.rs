@@ -1,6 +1,8 @@
+use crate::conditions;
 use crate::config::{Config, ScriptConfig};
 use crate::error::HookError;
 use crate::error::Result;
+use crate::template;
 
 use std::collections::HashMap;
 
@@ -32,6 +34,31 @@ impl ScriptManager {
         use std::thread::{self, JoinHandle};
         use std::time::Instant;
 
+        // Conditions and variables are evaluated before the terminal is
+        // taken over, so that their errors are printed normally. Commands
+        // whose condition doesn't hold are skipped, with the reason.
+        let mut resolved = Vec::new();
+        let mut skipped = Vec::new();
+        for cmd in self
+            .config
+            .scripts
+            .get(name)
+            .into_iter()
+            .flat_map(|s| &s.commands)
+        {
+            let unmet = cmd
+                .when
+                .as_ref()
+                .map(conditions::unmet)
+                .transpose()?
+                .flatten();
+            resolved.push(match unmet {
+                Some(_) => cmd.clone(),
+                None => template::resolve_command(cmd)?,
+            });
+            skipped.push(unmet);
+        }
+
         // Create a mutex-wrapped stdout for thread-safe access
         let stdout = Arc::new(Mutex::new(stdout()));
 
@@ -59,7 +86,7 @@ impl ScriptManager {
             return Ok(());
         }
 
-        let commands = Arc::new(script_config.commands.clone());
+        let commands = Arc::new(resolved);
         let mut handles: Vec<JoinHandle<Result<()>>> = vec![]; // Add type annotation here
 
         // Calculate screen layout
@@ -89,6 +116,14 @@ impl ScriptManager {
                             .unwrap_or_default()
                     )),
                 )?;
+                if let Some(unmet) = &skipped[idx] {
+                    execute!(
+                        stdout,
+                        cursor::MoveTo(1, y_pos + 1),
+                        SetForegroundColor(Color::DarkGrey),
+                        Print(format!("skipped, when {}", unmet)),
+                    )?;
+                }
             }
         }
 
@@ -98,6 +133,9 @@ impl ScriptManager {
         // Execute commands
         // Execute commands
         for (idx, cmd) in commands.iter().enumerate() {
+            if skipped[idx].is_some() {
+                continue;
+            }
             let cmd = cmd.clone();
             let name = name.to_string();
             let stdout = Arc::clone(&stdout);
@@ -176,7 +214,7 @@ impl ScriptManager {
 
                 if let Some(child_stdout) = child.stdout.take() {
                     let reader = std::io::BufReader::new(child_stdout);
-                    for line in std::io::BufRead::lines(reader).flatten() {
+                    for line in std::io::BufRead::lines(reader).map_while(std::result::Result::ok) {
                         let mut stdout = stdout.lock();
                         execute!(
                             stdout,
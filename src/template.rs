@@ -0,0 +1,189 @@
+use crate::config::CommandConfig;
+use crate::error::{HookError, Result};
+use crate::git::GitRepo;
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Matches `${name}`, and `$${name}`, which stands for a literal `${name}`
+/// passed on to the shell
+pub fn variable_pattern() -> Regex {
+    Regex::new(r"\$?\$\{([^}]+)\}").unwrap()
+}
+
+/// Whether a match of `variable_pattern` is an escaped `$${name}`
+pub fn is_escaped(caps: &Captures) -> bool {
+    caps[0].starts_with("$$")
+}
+
+/// Whether `${name}` is one of the variables available in every command:
+/// `${env:NAME}`, `${git:...}`, `${os}` or `${arch}`
+pub fn is_template(name: &str) -> bool {
+    matches!(name, "os" | "arch") || name.starts_with("env:") || name.starts_with("git:")
+}
+
+/// Why a template variable can't be expanded, if it can't
+pub fn problem(name: &str) -> Option<String> {
+    if let Some(spec) = name.strip_prefix("env:") {
+        let (variable, _) = spec.split_once(":-").unwrap_or((spec, ""));
+        let valid = !variable.is_empty()
+            && !variable.starts_with(|c: char| c.is_ascii_digit())
+            && variable
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_');
+        return (!valid).then(|| format!("invalid environment variable name '{}'", variable));
+    }
+
+    match name {
+        "os" | "arch" | "git:branch" | "git:root" => None,
+        _ if name.starts_with("git:") => Some(format!(
+            "unknown variable '${{{}}}', use ${{git:branch}} or ${{git:root}}",
+            name
+        )),
+        _ => Some(unsupported(name)),
+    }
+}
+
+/// The problem with a `${name}` nothing defines, suggesting the environment
+/// variable or shell variable it might mean
+pub fn unsupported(name: &str) -> String {
+    if name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        format!(
+            "unsupported variable '${{{0}}}', write ${{env:{0}}} for the environment variable \
+             or $${{{0}}} to leave it to the shell",
+            name
+        )
+    } else {
+        format!("unsupported variable '${{{}}}'", name)
+    }
+}
+
+/// The value of a template variable. None if `name` isn't one.
+pub fn lookup(name: &str) -> Option<Result<String>> {
+    if !is_template(name) {
+        return None;
+    }
+    if let Some(problem) = problem(name) {
+        return Some(Err(HookError::ConfigError(problem)));
+    }
+
+    let value = match name {
+        "os" => Ok(std::env::consts::OS.to_string()),
+        "arch" => Ok(std::env::consts::ARCH.to_string()),
+        // Empty on a detached HEAD, e.g. during a rebase
+        "git:branch" => GitRepo::current_branch().map(Option::unwrap_or_default),
+        "git:root" => GitRepo::toplevel().map(|root| root.display().to_string()),
+        _ => env(&name["env:".len()..]),
+    };
+    Some(value)
+}
+
+// `NAME` or `NAME:-default`; like the shell, the default also replaces an
+// empty value
+fn env(spec: &str) -> Result<String> {
+    let (name, default) = match spec.split_once(":-") {
+        Some((name, default)) => (name, Some(default)),
+        None => (spec, None),
+    };
+
+    match (std::env::var(name), default) {
+        (Ok(value), Some(default)) if value.is_empty() => Ok(default.to_string()),
+        (Ok(value), _) => Ok(value),
+        (Err(_), Some(default)) => Ok(default.to_string()),
+        (Err(_), None) => Err(HookError::ConfigError(format!(
+            "Environment variable '{}' isn't set. Set it, or give a default with \
+             '${{env:{}:-<default>}}'",
+            name, name
+        ))),
+    }
+}
+
+/// Replaces the template variables in `text`. Any other `${...}` is an
+/// error, since only hook commands have variables of their own.
+pub fn expand(text: &str) -> Result<String> {
+    let mut expanded = String::new();
+    let mut last = 0;
+    for caps in variable_pattern().captures_iter(text) {
+        let whole = caps.get(0).unwrap();
+        expanded.push_str(&text[last..whole.start()]);
+        last = whole.end();
+
+        if is_escaped(&caps) {
+            expanded.push_str(&whole.as_str()[1..]);
+            continue;
+        }
+        match lookup(&caps[1]) {
+            Some(value) => expanded.push_str(&value?),
+            None => {
+                return Err(HookError::ConfigError(format!(
+                    "Unknown variable '{}' in '{}'",
+                    whole.as_str(),
+                    text
+                )))
+            }
+        }
+    }
+    expanded.push_str(&text[last..]);
+    Ok(expanded)
+}
+
+/// A script command with its variables expanded and its `env_file` read
+/// into `env`, where the command's own `env` takes precedence
+pub fn resolve_command(command: &CommandConfig) -> Result<CommandConfig> {
+    let mut env = match &command.env_file {
+        Some(file) => read_env_file(file)?.into_iter().collect(),
+        None => HashMap::new(),
+    };
+    for (key, value) in &command.env {
+        env.insert(key.clone(), expand(value)?);
+    }
+
+    Ok(CommandConfig {
+        command: expand(&command.command)?,
+        env,
+        env_file: None,
+        ..command.clone()
+    })
+}
+
+/// Reads a `.env` file of `NAME=value` lines. Blank lines and `#` comments
+/// are skipped, `export` is allowed, and values may be quoted.
+pub fn read_env_file(path: &Path) -> Result<Vec<(String, String)>> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        HookError::ConfigError(format!("Can't read env file '{}': {}", path.display(), e))
+    })?;
+
+    let mut vars = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((name, value)) = line.split_once('=') else {
+            return Err(HookError::ConfigError(format!(
+                "{}:{}: expected NAME=value",
+                path.display(),
+                number + 1
+            )));
+        };
+
+        let value = value.trim();
+        let value = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) if value.len() > 1 && value.ends_with(quote) => {
+                value[1..value.len() - 1].to_string()
+            }
+            // An unquoted value ends at a comment
+            _ => value
+                .split(" #")
+                .next()
+                .unwrap_or_default()
+                .trim_end()
+                .to_string(),
+        };
+        vars.push((name.trim().to_string(), value));
+    }
+    Ok(vars)
+}
@@ -0,0 +1,137 @@
+use crate::config::{Config, LinterConfig};
+use crate::error::Result;
+use crate::presets;
+use std::path::Path;
+
+/// Starting configurations for `thira hooks init`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Template {
+    Rust,
+    Node,
+    Python,
+    Go,
+    /// Only commit message validation
+    Minimal,
+}
+
+// Manifest files identifying each kind of project, checked in order
+const MANIFESTS: &[(&str, Template)] = &[
+    ("Cargo.toml", Template::Rust),
+    ("package.json", Template::Node),
+    ("pyproject.toml", Template::Python),
+    ("setup.py", Template::Python),
+    ("requirements.txt", Template::Python),
+    ("go.mod", Template::Go),
+];
+
+// Rust and Node.js projects get the hooks of their presets
+const RUST: &str = r#"
+scripts:
+  check:
+    commands:
+      - command: cargo fmt --all -- --check
+      - command: cargo clippy --all-targets -- -D warnings
+      - command: cargo test
+"#;
+
+const NODE: &str = r#"
+scripts:
+  check:
+    commands:
+      - command: npx prettier --check .
+      - command: npx eslint .
+      - command: npm test
+"#;
+
+const PYTHON: &str = r#"
+hooks:
+  pre-commit:
+    - id: ruff-format
+      command: ruff format --check ${files}
+      glob: "*.py"
+    - id: ruff
+      command: ruff check ${files}
+      glob: "*.py"
+  pre-push:
+    - id: pytest
+      command: pytest
+scripts:
+  check:
+    commands:
+      - command: ruff format --check .
+      - command: ruff check .
+      - command: pytest
+"#;
+
+const GO: &str = r#"
+hooks:
+  pre-commit:
+    - id: gofmt
+      command: test -z "$(gofmt -l ${files})"
+      glob: "*.go"
+    - id: go-vet
+      command: go vet ./...
+      glob: "*.go"
+  pre-push:
+    - id: go-test
+      command: go test ./...
+scripts:
+  check:
+    commands:
+      - command: test -z "$(gofmt -l .)"
+      - command: go vet ./...
+      - command: go test ./...
+"#;
+
+const MINIMAL: &str = "{}";
+
+impl Template {
+    /// The template for the project in the current directory, with the
+    /// manifest file that gave it away
+    pub fn detect() -> Option<(Self, &'static str)> {
+        MANIFESTS
+            .iter()
+            .find(|(manifest, _)| Path::new(manifest).exists())
+            .map(|(manifest, template)| (*template, *manifest))
+    }
+
+    /// The configuration this template starts a repository with. Every
+    /// template validates commit messages with the default types.
+    pub fn config(self) -> Result<Config> {
+        let (preset, content) = match self {
+            Self::Rust => (presets::find("rust"), RUST),
+            Self::Node => (presets::find("node"), NODE),
+            Self::Python => (None, PYTHON),
+            Self::Go => (None, GO),
+            Self::Minimal => (None, MINIMAL),
+        };
+        let mut config: Config = serde_yaml::from_str(content)?;
+        if let Some(preset) = preset {
+            config.hooks = serde_yaml::from_str::<Config>(preset)?.hooks;
+        }
+
+        let mut defaults = Config::default();
+        if let Some(commit_msg) = defaults.hooks.remove("commit-msg") {
+            config.hooks.insert("commit-msg".to_string(), commit_msg);
+        }
+        config.lint = LinterConfig {
+            types: defaults.lint.types,
+            ..LinterConfig::default()
+        };
+        config.options = defaults.options;
+        Ok(config)
+    }
+}
+
+impl std::fmt::Display for Template {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Rust => "Rust",
+            Self::Node => "Node.js",
+            Self::Python => "Python",
+            Self::Go => "Go",
+            Self::Minimal => "minimal",
+        };
+        write!(f, "{}", name)
+    }
+}
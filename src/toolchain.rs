@@ -0,0 +1,191 @@
+use crate::config::{Hook, Language};
+use crate::error::{HookError, Result};
+use colored::*;
+use std::path::PathBuf;
+use std::process::Command;
+
+// Written once an environment is fully installed, so an interrupted
+// install is redone instead of leaving a half-populated environment
+const READY_MARKER: &str = ".athira-ready";
+
+/// An isolated environment holding a hook's toolchain and dependencies.
+/// Hooks with the same language and dependencies share one environment.
+#[derive(Debug)]
+pub struct Environment {
+    pub language: Language,
+    pub dir: PathBuf,
+    dependencies: Vec<String>,
+}
+
+impl Environment {
+    /// The environment a hook runs in, or None for `system` hooks
+    pub fn for_hook(hook: &Hook) -> Result<Option<Self>> {
+        if hook.language == Language::System {
+            return Ok(None);
+        }
+
+        let mut dependencies = hook.additional_dependencies.clone();
+        dependencies.sort();
+        dependencies.dedup();
+
+        let key = std::iter::once(hook.language.to_string())
+            .chain(dependencies.iter().cloned())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let dir = cache_dir()?.join(format!("{}-{:016x}", hook.language, fnv1a(&key)));
+
+        Ok(Some(Self {
+            language: hook.language,
+            dir,
+            dependencies,
+        }))
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.dir.join(READY_MARKER).exists()
+    }
+
+    /// Creates the environment and installs its dependencies unless that
+    /// was already done
+    pub fn ensure(&self) -> Result<()> {
+        if self.is_ready() {
+            return Ok(());
+        }
+
+        if self.dir.exists() {
+            std::fs::remove_dir_all(&self.dir).map_err(|e| HookError::FileError {
+                path: self.dir.clone(),
+                source: e,
+            })?;
+        }
+        std::fs::create_dir_all(&self.dir).map_err(|e| HookError::FileError {
+            path: self.dir.clone(),
+            source: e,
+        })?;
+
+        let deps = if self.dependencies.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", self.dependencies.join(", "))
+        };
+        eprintln!(
+            "{}",
+            format!("Installing {} environment{}...", self.language, deps).blue()
+        );
+
+        let dir = self.dir.display().to_string();
+        let result = match self.language {
+            Language::System => Ok(()),
+            Language::Python => self
+                .install(&["python3", "-m", "venv", &dir])
+                .and_then(|_| {
+                    if self.dependencies.is_empty() {
+                        return Ok(());
+                    }
+                    let pip = self.dir.join("bin").join("pip").display().to_string();
+                    let mut args = vec![pip.as_str(), "install", "--quiet"];
+                    args.extend(self.dependencies.iter().map(String::as_str));
+                    self.install(&args)
+                }),
+            Language::Node => {
+                if self.dependencies.is_empty() {
+                    Ok(())
+                } else {
+                    let mut args = vec![
+                        "npm",
+                        "install",
+                        "--prefix",
+                        &dir,
+                        "--no-audit",
+                        "--no-fund",
+                    ];
+                    args.extend(self.dependencies.iter().map(String::as_str));
+                    self.install(&args)
+                }
+            }
+            Language::Rust => self.dependencies.iter().try_for_each(|dependency| {
+                self.install(&["cargo", "install", "--quiet", "--root", &dir, dependency])
+            }),
+        };
+
+        if let Err(e) = result {
+            let _ = std::fs::remove_dir_all(&self.dir);
+            return Err(e);
+        }
+
+        std::fs::write(self.dir.join(READY_MARKER), "")?;
+        Ok(())
+    }
+
+    /// Environment variables that make the hook's command use this
+    /// environment's tools
+    pub fn vars(&self) -> Vec<(String, String)> {
+        let bin = match self.language {
+            Language::Node => self.dir.join("node_modules").join(".bin"),
+            _ => self.dir.join("bin"),
+        };
+
+        let mut paths = vec![bin];
+        paths.extend(
+            std::env::var_os("PATH")
+                .map_or_else(Vec::new, |path| std::env::split_paths(&path).collect()),
+        );
+        let path = std::env::join_paths(paths)
+            .map(|path| path.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let mut vars = vec![("PATH".to_string(), path)];
+        match self.language {
+            Language::Python => {
+                vars.push(("VIRTUAL_ENV".to_string(), self.dir.display().to_string()));
+            }
+            Language::Node => {
+                let modules = self.dir.join("node_modules");
+                vars.push(("NODE_PATH".to_string(), modules.display().to_string()));
+            }
+            Language::Rust | Language::System => {}
+        }
+        vars
+    }
+
+    fn install(&self, args: &[&str]) -> Result<()> {
+        let (program, rest) = args.split_first().expect("install command is never empty");
+        let output = Command::new(program).args(rest).output().map_err(|e| {
+            self.error(format!(
+                "Failed to run '{}' (is it installed?): {}",
+                program, e
+            ))
+        })?;
+
+        if !output.status.success() {
+            return Err(self.error(format!(
+                "'{}' failed:\n{}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+
+    fn error(&self, reason: String) -> HookError {
+        HookError::ToolchainError {
+            language: self.language.to_string(),
+            reason,
+        }
+    }
+}
+
+/// Directory holding the environments of all projects
+pub fn cache_dir() -> Result<PathBuf> {
+    dirs::cache_dir()
+        .map(|dir| dir.join("athira").join("envs"))
+        .ok_or_else(|| HookError::ConfigError("Could not determine the cache directory".into()))
+}
+
+// A stable hash for naming environments; std's hasher may change between
+// Rust releases, which would orphan existing environments
+fn fnv1a(value: &str) -> u64 {
+    value.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
@@ -0,0 +1,734 @@
+use crate::builtins::{self, Builtin};
+use crate::conditions;
+use crate::config::{Condition, Config, Language};
+use crate::document::{self, Key};
+use crate::error::{HookError, Result};
+use crate::files::FileFilter;
+use crate::formats::Format;
+use crate::git::hook_spec;
+use crate::hooks::variable_problem;
+use crate::layers::{self, Layers, Source};
+use crate::schema;
+use crate::template;
+use regex::Regex;
+use serde_json::Value as Json;
+use serde_yaml::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// A problem in the configuration and where it is
+#[derive(Debug)]
+pub struct Problem {
+    path: Vec<Key>,
+    pub message: String,
+    pub location: Option<Location>,
+}
+
+#[derive(Debug)]
+pub struct Location {
+    pub file: PathBuf,
+    /// Line and column, unknown for TOML and JSON files
+    pub position: Option<(usize, usize)>,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = document::dotted(&self.path);
+        match &self.location {
+            Some(Location {
+                file,
+                position: Some((line, column)),
+            }) => write!(
+                f,
+                "{}:{}:{}: {}",
+                file.display(),
+                line,
+                column,
+                self.message
+            ),
+            Some(Location { file, .. }) if !path.is_empty() => {
+                write!(f, "{}: {}: {}", file.display(), path, self.message)
+            }
+            Some(Location { file, .. }) => write!(f, "{}: {}", file.display(), self.message),
+            None if !path.is_empty() => write!(f, "{}: {}", path, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Every problem in the configuration files and the configuration they
+/// make up together
+pub fn check() -> Result<Vec<Problem>> {
+    let mut problems = check_files()?;
+
+    // Files that don't parse leave nothing to check together
+    let Ok(layers) = Layers::load(serde_yaml::to_value(Config::default())?) else {
+        return Ok(problems);
+    };
+    if let Some(config) = checkable(&layers.value) {
+        let mut found = check_config(&config);
+        locate(&mut found, &layers)?;
+        problems.extend(found);
+    }
+    Ok(problems)
+}
+
+// The configuration without the values the schema rejects, so what's left
+// can still be checked. Values that can't just be left out take the list
+// item they're in along with them. None if it still doesn't load.
+fn checkable(value: &Value) -> Option<Config> {
+    if let Ok(config) = serde_yaml::from_value(value.clone()) {
+        return Some(config);
+    }
+
+    let schema = schema::schema();
+    let mut found = Vec::new();
+    check_schema(value, &schema, &schema, &mut Vec::new(), &mut found);
+    let exact: fn(&[Key]) -> Vec<Key> = |path| path.to_vec();
+    let item: fn(&[Key]) -> Vec<Key> = |path| {
+        let end = path.iter().rposition(|key| matches!(key, Key::Index(_)));
+        path[..end.map_or(path.len(), |end| end + 1)].to_vec()
+    };
+
+    for prune in [exact, item] {
+        let mut value = value.clone();
+        // From the end, so removing a list item doesn't shift the others
+        let mut paths = found
+            .iter()
+            .map(|(path, _)| prune(path))
+            .collect::<Vec<_>>();
+        paths.sort_by(|a, b| b.cmp(a));
+        paths.dedup();
+        for path in &paths {
+            document::remove_at(&mut value, path);
+        }
+        if let Ok(config) = serde_yaml::from_value(value) {
+            return Some(config);
+        }
+    }
+    None
+}
+
+/// Checks each configuration file on its own: syntax, unknown keys and the
+/// types of values
+pub fn check_files() -> Result<Vec<Problem>> {
+    let schema = schema::schema();
+    let mut problems = Vec::new();
+
+    // A file that doesn't parse can't say what it extends, so it's checked
+    // on its own to report where it breaks
+    let sources = match layers::files() {
+        Ok(files) => files.into_iter().map(|(source, _)| source).collect(),
+        Err(_) => layers::sources(),
+    };
+
+    let mut files: Vec<PathBuf> = Vec::new();
+    for source in sources {
+        let Some(file) = source.path().filter(|path| path.exists()) else {
+            continue;
+        };
+        if files.contains(&file) {
+            continue;
+        }
+        files.push(file.clone());
+
+        let content = read(&file)?;
+        let value = if Format::of(&file) != Format::Yaml {
+            match source.read() {
+                Ok(value) => value,
+                Err(e) => {
+                    problems.push(Problem {
+                        path: Vec::new(),
+                        message: message(e),
+                        location: None,
+                    });
+                    continue;
+                }
+            }
+        } else {
+            match serde_yaml::from_str::<Value>(&content) {
+                Ok(value) => Some(value),
+                Err(e) => {
+                    problems.push(Problem {
+                        path: Vec::new(),
+                        message: e.to_string(),
+                        location: Some(Location {
+                            file: file.clone(),
+                            position: e.location().map(|at| (at.line(), at.column())),
+                        }),
+                    });
+                    continue;
+                }
+            }
+        };
+        let Some(value) = value else {
+            continue;
+        };
+
+        let mut found = Vec::new();
+        check_schema(&value, &schema, &schema, &mut Vec::new(), &mut found);
+        problems.extend(found.into_iter().map(|(path, message)| Problem {
+            location: Some(Location {
+                file: file.clone(),
+                position: position(&file, &content, &path),
+            }),
+            path,
+            message,
+        }));
+    }
+
+    Ok(problems)
+}
+
+/// Checks a configuration that isn't written yet, like the result of
+/// `thira config set`, against the schema
+pub fn check_value(value: &Value) -> Vec<Problem> {
+    let schema = schema::schema();
+    let mut found = Vec::new();
+    check_schema(value, &schema, &schema, &mut Vec::new(), &mut found);
+    found
+        .into_iter()
+        .map(|(path, message)| Problem {
+            path,
+            message,
+            location: None,
+        })
+        .collect()
+}
+
+/// Checks what the schema can't: hook names, references, patterns and
+/// settings that depend on each other. Problems aren't located yet.
+pub fn check_config(config: &Config) -> Vec<Problem> {
+    let mut checker = Checker {
+        config,
+        problems: Vec::new(),
+    };
+    checker.repos();
+    checker.hooks();
+    checker.scripts();
+    checker.options();
+    checker.lint();
+    checker.patterns();
+    checker.problems
+}
+
+/// Finds the file, line and column each problem comes from
+pub fn locate(problems: &mut [Problem], layers: &Layers) -> Result<()> {
+    let mut contents = HashMap::new();
+
+    for problem in problems
+        .iter_mut()
+        .filter(|problem| problem.location.is_none())
+    {
+        // Values that were never set belong to the file setting their parent
+        let source = (1..=problem.path.len())
+            .rev()
+            .map(|len| layers.origin(&document::dotted(&problem.path[..len])))
+            .find(|source| *source != Source::Default);
+        let Some(file) = source.and_then(|source| source.path()) else {
+            continue;
+        };
+
+        if !contents.contains_key(&file) {
+            contents.insert(file.clone(), read(&file)?);
+        }
+        problem.location = Some(Location {
+            position: position(&file, &contents[&file], &problem.path),
+            file,
+        });
+    }
+    Ok(())
+}
+
+fn read(file: &Path) -> Result<String> {
+    std::fs::read_to_string(file).map_err(|e| HookError::FileError {
+        path: file.to_path_buf(),
+        source: e,
+    })
+}
+
+fn position(file: &Path, content: &str, path: &[Key]) -> Option<(usize, usize)> {
+    if Format::of(file) != Format::Yaml {
+        return None;
+    }
+    document::position(content, path)
+}
+
+struct Checker<'a> {
+    config: &'a Config,
+    problems: Vec<Problem>,
+}
+
+impl Checker<'_> {
+    fn report(&mut self, path: Vec<Key>, message: impl Into<String>) {
+        self.problems.push(Problem {
+            path,
+            message: message.into(),
+            location: None,
+        });
+    }
+
+    fn repos(&mut self) {
+        for (i, repo) in self.config.repos.iter().enumerate() {
+            for (key, value) in [("repo", &repo.repo), ("rev", &repo.rev)] {
+                if value.is_empty() {
+                    self.report(
+                        path(&["repos"], [Key::Index(i), field(key)]),
+                        format!("'{}' can't be empty", key),
+                    );
+                }
+            }
+        }
+    }
+
+    fn hooks(&mut self) {
+        let config = self.config;
+        let mut names = config.hooks.keys().collect::<Vec<_>>();
+        names.sort();
+
+        for name in names {
+            let hooks = &config.hooks[name];
+            let base = path(&["hooks", name], []);
+            if hook_spec(name).is_none() {
+                self.report(base.clone(), format!("'{}' is not a git hook", name));
+            }
+            if hooks.is_empty() {
+                self.report(base.clone(), format!("hook '{}' has no commands", name));
+            }
+
+            let mut ids = HashSet::new();
+            for (i, hook) in hooks.iter().enumerate() {
+                let at = |key: &str| {
+                    let mut path = base.clone();
+                    path.extend([Key::Index(i), field(key)]);
+                    path
+                };
+
+                if let Some(id) = &hook.id {
+                    if !ids.insert(id) {
+                        self.report(at("id"), format!("duplicate id '{}'", id));
+                    }
+                }
+
+                if hook.command.trim().is_empty() && hook.uses.is_none() {
+                    self.report(at("command"), "empty command");
+                }
+                if !hook.command.is_empty() && hook.uses.is_some() {
+                    self.report(at("uses"), "can't set both 'command' and 'uses'");
+                }
+                if hook.uses.is_some() && config.repos.is_empty() {
+                    self.report(
+                        at("uses"),
+                        "uses a shared hook but no 'repos' are configured",
+                    );
+                }
+
+                if let Some(builtin) = hook.command.strip_prefix(builtins::PREFIX) {
+                    if let Err(e) = Builtin::from_name(builtin.trim()) {
+                        self.report(at("command"), message(e));
+                    }
+                }
+
+                if hook.language == Language::System && !hook.additional_dependencies.is_empty() {
+                    self.report(
+                        at("additional_dependencies"),
+                        "additional_dependencies need a 'language'",
+                    );
+                }
+                if hook.timeout == Some(0) {
+                    self.report(at("timeout"), "timeout must be greater than 0");
+                }
+
+                self.variables(at("command"), name, &hook.command);
+                for (j, arg) in hook.args.iter().enumerate() {
+                    let mut path = at("args");
+                    path.push(Key::Index(j));
+                    self.variables(path, name, arg);
+                }
+
+                for (key, patterns) in [("glob", &hook.glob), ("exclude", &hook.exclude)] {
+                    self.globs(at(key), patterns);
+                }
+                self.condition(at("when"), hook.when.as_ref());
+            }
+        }
+    }
+
+    fn variables(&mut self, path: Vec<Key>, hook: &str, text: &str) {
+        for caps in template::variable_pattern().captures_iter(text) {
+            if template::is_escaped(&caps) {
+                continue;
+            }
+            if let Some(problem) = variable_problem(self.config, hook, &caps[1]) {
+                self.report(path.clone(), problem);
+            }
+        }
+    }
+
+    // Scripts only have the variables available everywhere
+    fn template_variables(&mut self, path: Vec<Key>, text: &str) {
+        for caps in template::variable_pattern().captures_iter(text) {
+            if template::is_escaped(&caps) {
+                continue;
+            }
+            if let Some(problem) = template::problem(&caps[1]) {
+                self.report(path.clone(), problem);
+            }
+        }
+    }
+
+    fn scripts(&mut self) {
+        let config = self.config;
+        let mut names = config.scripts.keys().collect::<Vec<_>>();
+        names.sort();
+
+        for name in names {
+            let script = &config.scripts[name];
+            if script.max_threads == 0 {
+                self.report(
+                    path(&["scripts", name, "max_threads"], []),
+                    "max_threads must be greater than 0",
+                );
+            }
+            for (i, command) in script.commands.iter().enumerate() {
+                let at =
+                    |key: &str| path(&["scripts", name, "commands"], [Key::Index(i), field(key)]);
+                if command.command.trim().is_empty() {
+                    self.report(at("command"), "empty command");
+                }
+                self.template_variables(at("command"), &command.command);
+
+                self.condition(at("when"), command.when.as_ref());
+
+                let mut keys = command.env.keys().collect::<Vec<_>>();
+                keys.sort();
+                for key in keys {
+                    let mut path = at("env");
+                    path.push(field(key));
+                    self.template_variables(path, &command.env[key]);
+                }
+            }
+        }
+    }
+
+    fn options(&mut self) {
+        if self.config.options.hooks_dir.trim().is_empty() {
+            self.report(
+                path(&["options", "hooks_dir"], []),
+                "can't be empty, leave it out to use '.thira'",
+            );
+        } else if self.config.options.hooks_dir == ".git" {
+            self.report(
+                path(&["options", "hooks_dir"], []),
+                "can't be '.git' directly, use '.git/hooks' instead",
+            );
+        }
+    }
+
+    fn lint(&mut self) {
+        let lint = &self.config.lint;
+        if lint.min_subject_length == 0 {
+            self.report(
+                path(&["lint", "min_subject_length"], []),
+                "must be greater than 0",
+            );
+        }
+        if lint.max_subject_length < lint.min_subject_length {
+            self.report(
+                path(&["lint", "max_subject_length"], []),
+                "must be at least min_subject_length",
+            );
+        }
+        if lint.max_body_line_length == 0 {
+            self.report(
+                path(&["lint", "max_body_line_length"], []),
+                "must be greater than 0",
+            );
+        }
+    }
+
+    // Regexes and globs outside of hooks
+    fn patterns(&mut self) {
+        let config = self.config;
+
+        for (i, rule) in config.secrets.rules.iter().enumerate() {
+            if let Err(e) = Regex::new(&rule.regex) {
+                self.report(
+                    path(&["secrets", "rules"], [Key::Index(i), field("regex")]),
+                    regex_error(&rule.regex, e),
+                );
+            }
+        }
+
+        if let Some(naming) = &config.branches.naming {
+            if let Err(e) = Regex::new(naming) {
+                self.report(path(&["branches", "naming"], []), regex_error(naming, e));
+            }
+        }
+        self.globs(
+            path(&["branches", "protected"], []),
+            &config.branches.protected,
+        );
+
+        let policies = &config.policies;
+        self.globs(
+            path(&["policies", "forbidden_paths"], []),
+            &policies.forbidden_paths,
+        );
+        for (i, rule) in policies.companions.iter().enumerate() {
+            for (key, patterns) in [("when", &rule.when), ("require", &rule.require)] {
+                self.globs(
+                    path(&["policies", "companions"], [Key::Index(i), field(key)]),
+                    patterns,
+                );
+            }
+        }
+    }
+
+    fn condition(&mut self, base: Vec<Key>, condition: Option<&Condition>) {
+        let Some(condition) = condition else {
+            return;
+        };
+        let at = |key: &str, i: usize| {
+            let mut path = base.clone();
+            path.extend([field(key), Key::Index(i)]);
+            path
+        };
+        let entries = |entries: &[String]| {
+            entries
+                .iter()
+                .map(|entry| entry.strip_prefix('!').unwrap_or(entry).to_string())
+                .enumerate()
+                .collect::<Vec<_>>()
+        };
+
+        for (i, os) in entries(&condition.os) {
+            if !conditions::OPERATING_SYSTEMS.contains(&os.as_str()) {
+                self.report(
+                    at("os", i),
+                    format!(
+                        "unknown os '{}', expected one of: {}",
+                        os,
+                        conditions::OPERATING_SYSTEMS.join(", ")
+                    ),
+                );
+            }
+        }
+        for (i, pattern) in entries(&condition.branch) {
+            if let Err(e) = conditions::branch_glob(&pattern) {
+                self.report(at("branch", i), message(e));
+            }
+        }
+        for (i, entry) in entries(&condition.env) {
+            let name = entry
+                .split_once('=')
+                .map_or(entry.as_str(), |(name, _)| name);
+            if name.is_empty() {
+                self.report(at("env", i), "expected NAME or NAME=value");
+            }
+        }
+    }
+
+    fn globs(&mut self, base: Vec<Key>, patterns: &[String]) {
+        for (i, pattern) in patterns.iter().enumerate() {
+            if let Err(e) = FileFilter::new(std::slice::from_ref(pattern), &[]) {
+                let mut path = base.clone();
+                path.push(Key::Index(i));
+                self.report(path, message(e));
+            }
+        }
+    }
+}
+
+fn field(name: &str) -> Key {
+    Key::Field(name.to_string())
+}
+
+fn path(fields: &[&str], rest: impl IntoIterator<Item = Key>) -> Vec<Key> {
+    fields.iter().map(|name| field(name)).chain(rest).collect()
+}
+
+fn regex_error(pattern: &str, error: regex::Error) -> String {
+    // Only the last line, without the pattern drawn above it
+    let error = error.to_string();
+    let reason = error.lines().last().unwrap_or_default();
+    format!(
+        "invalid regex '{}': {}",
+        pattern,
+        reason.trim_start_matches("error: ")
+    )
+}
+
+// An error's message without the "Configuration error:" prefix
+fn message(error: HookError) -> String {
+    match error {
+        HookError::ConfigError(message) => message,
+        other => other.to_string(),
+    }
+}
+
+// Checks a value against the subset of JSON Schema used by `schema::schema`
+fn check_schema(
+    value: &Value,
+    schema: &Json,
+    root: &Json,
+    path: &mut Vec<Key>,
+    problems: &mut Vec<(Vec<Key>, String)>,
+) {
+    let schema = match schema.get("$ref").and_then(Json::as_str) {
+        Some(reference) => reference
+            .strip_prefix("#/definitions/")
+            .and_then(|name| root["definitions"].get(name))
+            .unwrap_or(schema),
+        None => schema,
+    };
+    if value.is_null() {
+        return;
+    }
+
+    if let Some(expected) = schema.get("type") {
+        let types = match expected {
+            Json::Array(types) => types.iter().filter_map(Json::as_str).collect(),
+            other => other.as_str().into_iter().collect::<Vec<_>>(),
+        };
+        if !types.iter().any(|kind| has_type(value, kind)) {
+            problems.push((
+                path.clone(),
+                format!(
+                    "expected {}, found {}",
+                    types.join(" or "),
+                    type_name(value)
+                ),
+            ));
+            return;
+        }
+    }
+
+    if let Some(options) = schema.get("enum").and_then(Json::as_array) {
+        let options = options.iter().filter_map(Json::as_str).collect::<Vec<_>>();
+        if !value.as_str().is_some_and(|value| options.contains(&value)) {
+            problems.push((
+                path.clone(),
+                format!("must be one of: {}", options.join(", ")),
+            ));
+            return;
+        }
+    }
+
+    if let (Some(minimum), Some(number)) =
+        (schema.get("minimum").and_then(Json::as_f64), value.as_f64())
+    {
+        if number < minimum {
+            problems.push((path.clone(), format!("must be at least {}", minimum)));
+        }
+    }
+
+    match value {
+        Value::Mapping(mapping) => {
+            let properties = schema.get("properties").and_then(Json::as_object);
+            for (key, child) in mapping {
+                let name = key.as_str().map(str::to_string).unwrap_or_else(|| {
+                    serde_yaml::to_string(key)
+                        .unwrap_or_default()
+                        .trim()
+                        .to_string()
+                });
+                path.push(Key::Field(name.clone()));
+
+                match properties.and_then(|properties| properties.get(&name)) {
+                    Some(child_schema) => check_schema(child, child_schema, root, path, problems),
+                    None => match schema.get("additionalProperties") {
+                        Some(Json::Bool(false)) => {
+                            let known = properties
+                                .map(|properties| properties.keys().map(String::as_str).collect())
+                                .unwrap_or_default();
+                            problems.push((path.clone(), unknown_key(&name, known)));
+                        }
+                        Some(additional @ Json::Object(_)) => {
+                            check_schema(child, additional, root, path, problems)
+                        }
+                        _ => {}
+                    },
+                }
+                path.pop();
+            }
+
+            for required in schema
+                .get("required")
+                .and_then(Json::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Json::as_str)
+            {
+                if !mapping.contains_key(required) {
+                    problems.push((path.clone(), format!("missing '{}'", required)));
+                }
+            }
+        }
+        Value::Sequence(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    path.push(Key::Index(i));
+                    check_schema(item, item_schema, root, path, problems);
+                    path.pop();
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn has_type(value: &Value, kind: &str) -> bool {
+    match kind {
+        "string" => value.is_string(),
+        "boolean" => value.is_bool(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "array" => value.is_sequence(),
+        "object" => value.is_mapping(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(number) if number.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Sequence(_) => "array",
+        Value::Mapping(_) => "object",
+        Value::Tagged(_) => "tagged value",
+    }
+}
+
+fn unknown_key(name: &str, known: Vec<&str>) -> String {
+    let suggestion = known
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= 2)
+        .min();
+
+    match suggestion {
+        Some((_, candidate)) => format!("unknown key '{}', did you mean '{}'?", name, candidate),
+        None => format!("unknown key '{}'", name),
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}